@@ -0,0 +1,381 @@
+use crate::{
+    config::{Config, DiscordConfig, SlackConfig, TokensConfig, WebhookConfig},
+    SharedData,
+};
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use arc_swap::ArcSwap;
+use ed25519_dalek::{Signature, VerifyingKey};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+
+/// Kinds of chat commands which webhooks may be allowed to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BotCommand {
+    /// Song title search.
+    Search,
+
+    /// `?` and `!` random queries.
+    Draw,
+
+    /// `list` and `+list` commands.
+    List,
+}
+
+impl BotCommand {
+    pub const ALL: &'static [BotCommand] =
+        &[BotCommand::Search, BotCommand::Draw, BotCommand::List];
+
+    /// Returns the command kinds a chat query line runs.
+    pub fn required_by(query: &str) -> Vec<BotCommand> {
+        if let Some(list_str) = query.strip_prefix("+list ") {
+            let list_query = list_str.trim().split_once(' ').map(|(_, q)| q.trim());
+            let mut commands = vec![BotCommand::List];
+            commands.extend(list_query.map(BotCommand::of_query));
+            commands
+        } else if query.starts_with("list ") {
+            vec![BotCommand::List]
+        } else {
+            vec![BotCommand::of_query(query)]
+        }
+    }
+
+    fn of_query(query: &str) -> BotCommand {
+        if query.starts_with('?') || query.starts_with('!') {
+            BotCommand::Draw
+        } else {
+            BotCommand::Search
+        }
+    }
+}
+
+/// Accepted tokens of webhooks and admin endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    webhooks: Vec<WebhookConfig>,
+    admin: String,
+    slack: Option<SlackConfig>,
+    discord: Option<(DiscordConfig, VerifyingKey)>,
+}
+
+/// Atomically swappable registry, so that tokens can be rotated without restarting.
+pub type TokenRegistryHandle = Arc<ArcSwap<TokenRegistry>>;
+
+impl TokenRegistry {
+    pub fn new(tokens: &TokensConfig) -> TokenRegistry {
+        let mut webhooks = tokens.webhooks.clone();
+        if let Some(token) = &tokens.mattermost {
+            webhooks.push(WebhookConfig {
+                name: "default".into(),
+                tokens: vec![token.clone()],
+                ..Default::default()
+            });
+        }
+
+        TokenRegistry {
+            webhooks,
+            admin: tokens.admin.clone(),
+            slack: tokens.slack.clone(),
+            discord: tokens.discord.clone().map(|d| {
+                let key = d.verifying_key().expect("should be validated");
+                (d, key)
+            }),
+        }
+    }
+
+    /// Finds the webhook which accepts the token from the team and the channel.
+    pub fn authorize_webhook(
+        &self,
+        token: &str,
+        team_id: &str,
+        channel_id: &str,
+    ) -> Option<&WebhookConfig> {
+        // every token of every webhook is compared so that timing does not tell which one matched
+        self.webhooks.iter().fold(None, |found, w| {
+            let token_matched = w
+                .tokens
+                .iter()
+                .fold(false, |matched, t| matched | token_eq(t, token));
+            let accepted = token_matched && w.accepts_channel(team_id, channel_id);
+            found.or(if accepted { Some(w) } else { None })
+        })
+    }
+
+    /// Checks the bearer token for admin endpoints. Always fails if the admin token is empty.
+    pub fn authorize_admin(&self, bearer: Option<&str>) -> bool {
+        match bearer {
+            Some(bearer) => !self.admin.is_empty() && token_eq(&self.admin, bearer),
+            None => false,
+        }
+    }
+
+    /// Verifies the `v0` signature of a Slack request, and that it is not replayed.
+    /// `now` is the current UNIX time in seconds.
+    pub fn authorize_slack(
+        &self,
+        timestamp: Option<&str>,
+        signature: Option<&str>,
+        body: &[u8],
+        now: u64,
+    ) -> Option<&SlackConfig> {
+        let slack = self.slack.as_ref()?;
+        let timestamp = timestamp?;
+        let sent_at: u64 = timestamp.parse().ok()?;
+        if now.abs_diff(sent_at) > slack.timestamp_tolerance {
+            return None;
+        }
+        let signature = hex::decode(signature?.strip_prefix("v0=")?).ok()?;
+
+        let verified = slack
+            .signing_secrets
+            .iter()
+            .fold(false, |verified, secret| {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                    .expect("HMAC should accept keys of any length");
+                mac.update(format!("v0:{timestamp}:").as_bytes());
+                mac.update(body);
+                verified | mac.verify_slice(&signature).is_ok()
+            });
+        verified.then_some(slack)
+    }
+
+    /// Verifies the Ed25519 signature of a Discord interaction, and that it is not replayed.
+    /// `now` is the current UNIX time in seconds.
+    pub fn authorize_discord(
+        &self,
+        timestamp: Option<&str>,
+        signature: Option<&str>,
+        body: &[u8],
+        now: u64,
+    ) -> Option<&DiscordConfig> {
+        let (discord, key) = self.discord.as_ref()?;
+        let timestamp = timestamp?;
+        let sent_at: u64 = timestamp.parse().ok()?;
+        if now.abs_diff(sent_at) > discord.timestamp_tolerance {
+            return None;
+        }
+        let signature_bytes: [u8; 64] = hex::decode(signature?).ok()?.try_into().ok()?;
+        let signature = Signature::from_bytes(&signature_bytes);
+
+        let message = [timestamp.as_bytes(), body].concat();
+        key.verify_strict(&message, &signature).ok()?;
+        Some(discord)
+    }
+
+    pub fn webhooks_count(&self) -> usize {
+        self.webhooks.len()
+    }
+}
+
+/// Compares tokens in time independent of their contents.
+pub fn token_eq(expected: &str, actual: &str) -> bool {
+    expected.as_bytes().ct_eq(actual.as_bytes()).into()
+}
+
+/// Reads the config again and swaps the token registry. Returns the number of webhooks.
+/// The current registry is kept if the config is invalid.
+/// Changes in other sections are reported but need a restart.
+pub fn reload_tokens(sd: &SharedData) -> Result<usize> {
+    let config = Config::load((*sd.arguments).clone())?;
+    let ignored_sections = config.changed_static_sections(&sd.config);
+    if !ignored_sections.is_empty() {
+        warn!(
+            "config changes in {} are not applied until restart",
+            ignored_sections.join(", ")
+        );
+    }
+
+    let registry = TokenRegistry::new(&config.tokens);
+    let webhooks_count = registry.webhooks_count();
+    sd.tokens.store(Arc::new(registry));
+
+    info!("tokens reloaded: {webhooks_count} webhooks");
+    Ok(webhooks_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenRegistry;
+    use crate::config::{DiscordConfig, SlackConfig, TokensConfig, WebhookConfig};
+
+    use ed25519_dalek::{Signer, SigningKey};
+
+    // example of https://api.slack.com/authentication/verifying-requests-from-slack
+    const SLACK_SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const SLACK_TIMESTAMP: &str = "1531420618";
+    const SLACK_BODY: &str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+    const SLACK_SIGNATURE: &str =
+        "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+
+    // key pair of the test 1 in RFC 8032
+    const DISCORD_SECRET_KEY: &str =
+        "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+    const DISCORD_PUBLIC_KEY: &str =
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+
+    fn registry(admin: &str) -> TokenRegistry {
+        TokenRegistry::new(&TokensConfig {
+            admin: admin.into(),
+            webhooks: vec![
+                WebhookConfig {
+                    name: "team".into(),
+                    tokens: vec!["old".into(), "new".into()],
+                    team_id: Some("t1".into()),
+                    channel_ids: vec!["c1".into()],
+                    ..Default::default()
+                },
+                WebhookConfig {
+                    name: "anywhere".into(),
+                    tokens: vec!["any".into()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        })
+    }
+
+    fn slack_registry(signing_secrets: &[&str]) -> TokenRegistry {
+        TokenRegistry::new(&TokensConfig {
+            slack: Some(SlackConfig {
+                signing_secrets: signing_secrets.iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn slack_verified(registry: &TokenRegistry, signature: &str, now: u64) -> bool {
+        registry
+            .authorize_slack(
+                Some(SLACK_TIMESTAMP),
+                Some(signature),
+                SLACK_BODY.as_bytes(),
+                now,
+            )
+            .is_some()
+    }
+
+    #[test]
+    fn authorizes_webhook_tokens_in_their_channels() {
+        let registry = registry("");
+        let name = |token, team_id, channel_id| {
+            registry
+                .authorize_webhook(token, team_id, channel_id)
+                .map(|w| w.name.as_str())
+        };
+        assert_eq!(name("old", "t1", "c1"), Some("team"));
+        assert_eq!(name("new", "t1", "c1"), Some("team"));
+        assert_eq!(name("any", "t2", "c9"), Some("anywhere"));
+        assert_eq!(name("new", "t1", "c2"), None);
+        assert_eq!(name("new", "t2", "c1"), None);
+        assert_eq!(name("unknown", "t1", "c1"), None);
+    }
+
+    #[test]
+    fn rejects_admin_bearer_if_admin_token_is_empty() {
+        assert!(!registry("").authorize_admin(Some("")));
+        assert!(!registry("").authorize_admin(None));
+        assert!(registry("adm").authorize_admin(Some("adm")));
+        assert!(!registry("adm").authorize_admin(Some("other")));
+        assert!(!registry("adm").authorize_admin(None));
+    }
+
+    #[test]
+    fn verifies_slack_documented_example() {
+        let registry = slack_registry(&[SLACK_SECRET]);
+        assert!(slack_verified(&registry, SLACK_SIGNATURE, 1531420618));
+        assert!(slack_verified(&registry, SLACK_SIGNATURE, 1531420618 + 300));
+        assert!(TokenRegistry::default()
+            .authorize_slack(
+                Some(SLACK_TIMESTAMP),
+                Some(SLACK_SIGNATURE),
+                SLACK_BODY.as_bytes(),
+                1531420618
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_stale_slack_timestamps() {
+        let registry = slack_registry(&[SLACK_SECRET]);
+        assert!(!slack_verified(
+            &registry,
+            SLACK_SIGNATURE,
+            1531420618 + 301
+        ));
+        assert!(!slack_verified(
+            &registry,
+            SLACK_SIGNATURE,
+            1531420618 - 301
+        ));
+    }
+
+    #[test]
+    fn rejects_slack_signatures_without_v0_prefix() {
+        let registry = slack_registry(&[SLACK_SECRET]);
+        let unprefixed = SLACK_SIGNATURE.trim_start_matches("v0=");
+        assert!(!slack_verified(&registry, unprefixed, 1531420618));
+        assert!(!slack_verified(
+            &registry,
+            &format!("v1={unprefixed}"),
+            1531420618
+        ));
+        assert!(!slack_verified(&registry, "v0=not hex", 1531420618));
+    }
+
+    #[test]
+    fn verifies_slack_with_rotated_secrets() {
+        let registry = slack_registry(&["new secret", SLACK_SECRET]);
+        assert!(slack_verified(&registry, SLACK_SIGNATURE, 1531420618));
+
+        let registry = slack_registry(&["new secret"]);
+        assert!(!slack_verified(&registry, SLACK_SIGNATURE, 1531420618));
+    }
+
+    #[test]
+    fn verifies_discord_signatures_by_public_key() {
+        let secret: [u8; 32] = hex::decode(DISCORD_SECRET_KEY).unwrap().try_into().unwrap();
+        let signing_key = SigningKey::from_bytes(&secret);
+        assert_eq!(
+            hex::encode(signing_key.verifying_key().as_bytes()),
+            DISCORD_PUBLIC_KEY
+        );
+
+        let registry = TokenRegistry::new(&TokensConfig {
+            discord: Some(DiscordConfig {
+                public_key: DISCORD_PUBLIC_KEY.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let timestamp = "1700000000";
+        let body = br#"{"type":1}"#;
+        let signature = hex::encode(
+            signing_key
+                .sign(&[timestamp.as_bytes(), body].concat())
+                .to_bytes(),
+        );
+        let verified = |timestamp, signature: &str, body: &[u8], now| {
+            registry
+                .authorize_discord(Some(timestamp), Some(signature), body, now)
+                .is_some()
+        };
+
+        assert!(verified(timestamp, &signature, body, 1700000000));
+        assert!(!verified(
+            timestamp,
+            &signature,
+            br#"{"type":2}"#,
+            1700000000
+        ));
+        assert!(!verified("1700000001", &signature, body, 1700000000));
+        assert!(!verified(timestamp, &signature, body, 1700000000 + 301));
+        assert!(!verified(timestamp, &signature[2..], body, 1700000000));
+    }
+}
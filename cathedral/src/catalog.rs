@@ -0,0 +1,114 @@
+use crate::{
+    auth::reload_tokens,
+    db::function::{fetch_alias_pairs, fetch_title_pair, open_sqlite_file},
+    suggest::SuggestIndex,
+    SharedData,
+};
+
+use std::{
+    fs::metadata,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use arc_swap::ArcSwap;
+use sqlx::SqlitePool;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    time::interval,
+};
+use tracing::{info, warn};
+
+/// Snapshot of the song catalog. Requests keep using the snapshot they started with.
+#[derive(Debug)]
+pub struct Catalog {
+    pub sqlite_pool: SqlitePool,
+    pub id_song_pairs: Vec<(i64, String)>,
+    pub suggest_index: SuggestIndex,
+}
+
+/// Atomically swappable catalog shared by all requests.
+pub type CatalogHandle = Arc<ArcSwap<Catalog>>;
+
+/// Opens the catalog file and builds in-memory indices.
+#[derive(Debug, Clone)]
+pub struct CatalogLoader {
+    pub sqlite_filename: PathBuf,
+    pub userdata_uri: String,
+}
+
+impl CatalogLoader {
+    pub async fn load(&self) -> Result<Catalog> {
+        let sqlite_pool = open_sqlite_file(&self.sqlite_filename, &self.userdata_uri).await?;
+        let id_song_pairs = fetch_title_pair(&sqlite_pool).await?;
+        let alias_pairs = fetch_alias_pairs(&sqlite_pool).await?;
+        let suggest_index = SuggestIndex::new(&id_song_pairs, &alias_pairs);
+
+        Ok(Catalog {
+            sqlite_pool,
+            id_song_pairs,
+            suggest_index,
+        })
+    }
+}
+
+/// Loads the catalog again and swaps it. Returns the number of songs.
+/// The current catalog is kept if loading fails.
+pub async fn reload_catalog(sd: &SharedData) -> Result<usize> {
+    let catalog = sd.catalog_loader.load().await?;
+    let songs_count = catalog.id_song_pairs.len();
+    sd.catalog.store(Arc::new(catalog));
+
+    info!("catalog reloaded: {songs_count} songs");
+    Ok(songs_count)
+}
+
+/// Reloads the catalog and tokens on SIGHUP.
+pub fn spawn_signal_reloader(sd: SharedData) -> Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            if let Err(err) = reload_catalog(&sd).await {
+                warn!("catalog reload failed: {err}");
+            }
+            if let Err(err) = reload_tokens(&sd) {
+                warn!("tokens reload failed: {err}");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Reloads the catalog when the modification time of the file changes.
+/// Waits until it stays the same for one interval, so that files being written are not loaded.
+pub fn spawn_file_reloader(sd: SharedData, poll_interval: Duration) {
+    tokio::spawn(async move {
+        let path = sd.catalog_loader.sqlite_filename.clone();
+        let mut last_modified = modified_time(&path);
+        let mut pending_modified = None;
+        let mut ticker = interval(poll_interval);
+        loop {
+            ticker.tick().await;
+            let modified = modified_time(&path);
+            if modified.is_none() || modified == last_modified {
+                continue;
+            }
+            if modified != pending_modified {
+                pending_modified = modified;
+                continue;
+            }
+
+            last_modified = modified;
+            if let Err(err) = reload_catalog(&sd).await {
+                warn!("catalog reload failed: {err}");
+            }
+        }
+    });
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,654 @@
+use crate::{
+    auth::BotCommand,
+    catalog::Catalog,
+    chat::schema::{
+        BpmRange, CommandContext, CommandError, CommandResult, DiffLine, Notice, SongCard,
+    },
+    config::Config,
+    db::{function::*, schema::*, userdata::*},
+    selection::{
+        choose_weighted, exclude_recent, HistoryMode, HistoryPenalty, HistoryScope, StrategyKind,
+        WeightingStrategy,
+    },
+};
+
+use rand::prelude::*;
+use sqlx::SqlitePool;
+use tracing::warn;
+
+pub type DiffId = (i64, PlaySide, Difficulty);
+
+/// Diff shown in a result, with the query line which draws another one in its place.
+pub type Slot = (DiffId, Option<String>);
+
+/// Runs chat command lines against the catalog and user data.
+#[derive(Debug, Clone, Copy)]
+pub struct CommandEngine<'a> {
+    pub config: &'a Config,
+    pub catalog: &'a Catalog,
+    pub userdata_pool: &'a SqlitePool,
+}
+
+impl CommandEngine<'_> {
+    /// Runs each line of the text and merges their results.
+    pub async fn run(
+        &self,
+        context: &CommandContext<'_>,
+        text: &str,
+    ) -> Result<CommandResult, CommandError> {
+        let queries = text.split('\n').map(|q| q.trim()).filter(|q| !q.is_empty());
+
+        let mut notices = vec![];
+        let mut song_ids = vec![];
+        let mut slots = vec![];
+        for query in queries {
+            for command in BotCommand::required_by(query) {
+                require_command(context, command)?;
+            }
+
+            if let Some(list_str) = query.strip_prefix("+list ") {
+                // add diffs to list
+                let Some((list_name, list_query)) = list_str.trim().split_once(' ') else {
+                    return Err(FilterQueryError::InvalidFormat.into());
+                };
+                let (scope, list_name) = parse_list_name(list_name);
+                let owner = context.filter_context().list_owner(scope);
+
+                let list_query = list_query.trim();
+                let query_diff_ids: Vec<_> = if let Some(filters_str) = list_query.strip_prefix('?')
+                {
+                    // every diff matching filters, instead of drawn ones
+                    let (filters, _, _) = parse_extended_query(filters_str)?;
+                    let candidates = query_filter_diffs(
+                        &self.catalog.sqlite_pool,
+                        &filters,
+                        context.filter_context(),
+                    )
+                    .await?;
+                    candidates.iter().map(|c| c.id()).collect()
+                } else {
+                    let (query_song_ids, query_slots) =
+                        self.resolve_query_line(context, list_query, false).await?;
+                    let song_diffs =
+                        fetch_diffs_by_song_ids(&self.catalog.sqlite_pool, &query_song_ids).await?;
+                    query_slots
+                        .into_iter()
+                        .map(|(id, _)| id)
+                        .chain(
+                            song_diffs
+                                .iter()
+                                .map(|d| (d.song_id, d.play_side, d.difficulty)),
+                        )
+                        .collect()
+                };
+
+                let list = create_list(self.userdata_pool, scope, owner, list_name).await?;
+                insert_list_entries(self.userdata_pool, list.id, &query_diff_ids).await?;
+
+                notices.push(Notice::ListUpdated {
+                    name: list.name,
+                    added: query_diff_ids.len(),
+                });
+                slots.extend(query_diff_ids.into_iter().map(|id| (id, None)));
+            } else if let Some(list_name) = query.strip_prefix("list ") {
+                // show list
+                let (scope, list_name) = parse_list_name(list_name.trim());
+                let owner = context.filter_context().list_owner(scope);
+                let list = fetch_list(self.userdata_pool, scope, owner, list_name)
+                    .await?
+                    .ok_or_else(|| CommandError::NotFound(format!("list {list_name}")))?;
+                let entries = fetch_list_entries(self.userdata_pool, list.id).await?;
+
+                notices.push(Notice::ListShown {
+                    name: list.name,
+                    count: entries.len(),
+                });
+                slots.extend(entries.into_iter().map(|id| (id, None)));
+            } else {
+                let (query_song_ids, query_slots) =
+                    self.resolve_query_line(context, query, true).await?;
+                song_ids.extend_from_slice(&query_song_ids);
+                slots.extend(query_slots);
+            }
+        }
+
+        self.build_result(notices, song_ids, slots).await
+    }
+
+    /// Finds the song closest to the title.
+    pub async fn search_title(
+        &self,
+        context: &CommandContext<'_>,
+        title: &str,
+    ) -> Result<CommandResult, CommandError> {
+        require_command(context, BotCommand::Search)?;
+        let song_id = self.match_title(title);
+        self.build_result(vec![], vec![song_id], vec![]).await
+    }
+
+    /// Draws diffs by filters in the `?` query syntax.
+    pub async fn draw_filtered(
+        &self,
+        context: &CommandContext<'_>,
+        filters_str: &str,
+        count: usize,
+    ) -> Result<CommandResult, CommandError> {
+        require_command(context, BotCommand::Draw)?;
+        let (filters, _, strategy) = parse_extended_query(filters_str)?;
+        let count = count.min(self.config.search.max_results);
+        let diff_ids = self
+            .draw_diffs(context, &filters, count, strategy, true)
+            .await?;
+        let redraw = format!("?{}", filters_str.trim());
+        let slots = diff_ids
+            .into_iter()
+            .map(|id| (id, Some(redraw.clone())))
+            .collect();
+        self.build_result(vec![], vec![], slots).await
+    }
+
+    /// Shows the song of the ID.
+    pub async fn show_song(
+        &self,
+        context: &CommandContext<'_>,
+        song_id: i64,
+    ) -> Result<CommandResult, CommandError> {
+        require_command(context, BotCommand::Search)?;
+        let result = self.build_result(vec![], vec![song_id], vec![]).await?;
+        if result.songs.is_empty() {
+            return Err(CommandError::NotFound(format!("song id {song_id}")));
+        }
+        Ok(result)
+    }
+
+    /// Draws another diff in place of the slot by its query line.
+    pub async fn redraw_slot(
+        &self,
+        context: &CommandContext<'_>,
+        slots: &mut [Slot],
+        index: usize,
+    ) -> Result<(), CommandError> {
+        require_command(context, BotCommand::Draw)?;
+        let Some((_, Some(redraw))) = slots.get(index) else {
+            return Err(CommandError::NotFound(format!("query of slot {index}")));
+        };
+
+        let (_, redrawn) = self
+            .resolve_query_line(context, &redraw.clone(), true)
+            .await?;
+        if let Some(slot) = redrawn.into_iter().next() {
+            slots[index] = slot;
+        }
+        Ok(())
+    }
+
+    /// Shows the slots and the songs again, such as after one of them is redrawn.
+    pub async fn rebuild(
+        &self,
+        slots: Vec<Slot>,
+        song_ids: Vec<i64>,
+    ) -> Result<CommandResult, CommandError> {
+        self.build_result(vec![], song_ids, slots).await
+    }
+
+    /// Fetches songs and diffs to show.
+    async fn build_result(
+        &self,
+        notices: Vec<Notice>,
+        song_ids: Vec<i64>,
+        slots: Vec<Slot>,
+    ) -> Result<CommandResult, CommandError> {
+        let sqlite_pool = &self.catalog.sqlite_pool;
+        let diff_ids: Vec<_> = slots.iter().map(|(id, _)| *id).collect();
+        let by_diff_diffs = fetch_diffs_by_ids(sqlite_pool, &diff_ids).await?;
+        let by_song_diffs = fetch_diffs_by_song_ids(sqlite_pool, &song_ids).await?;
+        let by_song_tiers = fetch_tiers_by_song_ids(sqlite_pool, &song_ids).await?;
+
+        let merged_song_ids: Vec<_> = song_ids
+            .iter()
+            .copied()
+            .chain(by_diff_diffs.iter().map(|d| d.song_id))
+            .collect();
+        let song_version_pairs = fetch_songs_with_versions(sqlite_pool, &merged_song_ids).await?;
+
+        // by_song_diffs have all diffs which correspond to songs in song_ids,
+        // so merged_song_ids don't have to care about it.
+
+        // slots keep their order, which fetched diffs don't
+        let mut diffs = vec![];
+        for ((song_id, play_side, difficulty), redraw) in slots {
+            let Some(diff) = by_diff_diffs.iter().find(|d| {
+                d.song_id == song_id && d.play_side == play_side && d.difficulty == difficulty
+            }) else {
+                continue;
+            };
+            let Some((song, version)) = song_version_pairs.iter().find(|(s, _)| s.id == song_id)
+            else {
+                continue;
+            };
+            diffs.push(DiffLine {
+                song_id,
+                redraw,
+                play_side: diff.play_side,
+                difficulty: diff.difficulty,
+                level: diff.level,
+                title: song.title.clone(),
+                version_abbrev: version.abbrev.clone(),
+            });
+        }
+
+        let mut songs = vec![];
+        for song_id in song_ids {
+            let Some((song, version)) = song_version_pairs.iter().find(|(s, _)| s.id == song_id)
+            else {
+                continue;
+            };
+            let song_diffs: Vec<_> = by_song_diffs
+                .iter()
+                .filter(|d| d.song_id == song.id)
+                .cloned()
+                .collect();
+            let tiers = by_song_tiers
+                .iter()
+                .filter(|t| t.song_id == song.id)
+                .cloned()
+                .collect();
+
+            songs.push(build_song_card(song, version, song_diffs, tiers));
+        }
+
+        Ok(CommandResult {
+            notices,
+            diffs,
+            songs,
+        })
+    }
+
+    /// Resolves a query line into matched songs and drawn diffs.
+    /// Draws are recorded to the history if `record` is true.
+    async fn resolve_query_line(
+        &self,
+        context: &CommandContext<'_>,
+        query: &str,
+        record: bool,
+    ) -> Result<(Vec<i64>, Vec<Slot>), CommandError> {
+        let mut song_ids = vec![];
+        let mut slots = vec![];
+        if let Some(filters_str) = query.strip_prefix('?') {
+            // diff filter query
+            let (filters, count, strategy) = parse_extended_query(filters_str)?;
+            let chosen_ids = self
+                .draw_diffs(context, &filters, count, strategy, record)
+                .await?;
+            let redraw = format!("?{}", filters_str.trim());
+            slots.extend(chosen_ids.into_iter().map(|id| (id, Some(redraw.clone()))));
+        } else if let Some(compact_str) = query.strip_prefix('!') {
+            // diff compact query
+            let (compact_queries, strategy) = parse_compact_queries(compact_str)?;
+
+            for (query, count, target) in compact_queries {
+                let chosen_ids = self
+                    .draw_diffs(context, &query, count, strategy, record)
+                    .await?;
+                let redraw = format!("!{target} w:{strategy}");
+                slots.extend(chosen_ids.into_iter().map(|id| (id, Some(redraw.clone()))));
+            }
+        } else {
+            // song title
+            song_ids.push(self.match_title(query));
+        }
+
+        Ok((song_ids, slots))
+    }
+
+    fn match_title(&self, query: &str) -> i64 {
+        let searcher = self.config.search.costs.searcher();
+        let mut candidate_distasnce = isize::MAX;
+        let mut candidate_id = 0;
+
+        for (id, title) in &self.catalog.id_song_pairs[..] {
+            let distance = searcher.distance(query, title);
+            if distance < candidate_distasnce {
+                candidate_distasnce = distance;
+                candidate_id = *id;
+            }
+        }
+
+        candidate_id
+    }
+
+    /// Draws diffs matching filters with the weighting strategy.
+    /// They are recorded to the history if `record` is true.
+    async fn draw_diffs(
+        &self,
+        context: &CommandContext<'_>,
+        filters: &[FilterQuery],
+        count: usize,
+        strategy: StrategyKind,
+        record: bool,
+    ) -> Result<Vec<DiffId>, CommandError> {
+        let candidates =
+            query_filter_diffs(&self.catalog.sqlite_pool, filters, context.filter_context())
+                .await?;
+        let window = self.config.history.window;
+        let owner = self.history_owner(context);
+        let recent_draws = match owner {
+            Some((channel_id, user_id)) => {
+                fetch_recent_draws(self.userdata_pool, channel_id, user_id, window).await?
+            }
+            None => vec![],
+        };
+
+        let chosen_ids: Vec<_> = {
+            let (candidates, weighting) = match self.config.history.mode {
+                HistoryMode::Off => (candidates, strategy.build(&recent_draws)),
+                HistoryMode::Exclude => (
+                    exclude_recent(candidates, &recent_draws, count),
+                    strategy.build(&recent_draws),
+                ),
+                HistoryMode::Deweight => {
+                    let inner = strategy.build(&recent_draws);
+                    let penalized: Box<dyn WeightingStrategy> =
+                        Box::new(HistoryPenalty::new(inner, &recent_draws));
+                    (candidates, penalized)
+                }
+            };
+            choose_weighted(&mut thread_rng(), &*weighting, &candidates, count)
+                .into_iter()
+                .map(|c| c.id())
+                .collect()
+        };
+
+        if let Some((channel_id, user_id)) = owner.filter(|_| record) {
+            insert_draws(
+                self.userdata_pool,
+                context.channel_id,
+                context.user_id,
+                &chosen_ids,
+            )
+            .await?;
+            prune_draws(self.userdata_pool, channel_id, user_id, window).await?;
+        }
+
+        Ok(chosen_ids)
+    }
+
+    /// Channel and user filters of the sender's draw history. `None` if the sender is unknown.
+    fn history_owner<'a>(
+        &self,
+        context: &CommandContext<'a>,
+    ) -> Option<(Option<&'a str>, Option<&'a str>)> {
+        match self.config.history.scope {
+            HistoryScope::Channel if !context.channel_id.is_empty() => {
+                Some((Some(context.channel_id), None))
+            }
+            // anonymous requests must not share one history
+            _ if context.user_id.is_empty() => None,
+            // requests without channel (e.g. Discord DMs) keep history per user
+            HistoryScope::Channel => Some((Some(""), Some(context.user_id))),
+            HistoryScope::User => Some((None, Some(context.user_id))),
+        }
+    }
+}
+
+pub fn require_command(
+    context: &CommandContext<'_>,
+    command: BotCommand,
+) -> Result<(), CommandError> {
+    if context.commands.contains(&command) {
+        return Ok(());
+    }
+
+    warn!(
+        "Command {command:?} is not allowed for {}",
+        context.user_name
+    );
+    Err(CommandError::Forbidden(command))
+}
+
+/// Splits diffs of the song into SP and DP, and collects charts with their own tempo.
+pub fn build_song_card(
+    song: &Song,
+    version: &Version,
+    diffs: Vec<Diff>,
+    tiers: Vec<Tier>,
+) -> SongCard {
+    // charts with their own tempo follow the song's one
+    let bpm = if song.bpm_indeterminate {
+        BpmRange {
+            min: None,
+            max: None,
+        }
+    } else {
+        BpmRange {
+            min: song.min_bpm,
+            max: song.max_bpm,
+        }
+    };
+    let chart_bpms = diffs
+        .iter()
+        .map(|d| {
+            let chart_bpm = BpmRange {
+                min: d.min_bpm,
+                max: d.max_bpm,
+            };
+            (d.play_side, d.difficulty, chart_bpm)
+        })
+        .filter(|(_, _, chart_bpm)| *chart_bpm != bpm)
+        .collect();
+    let (sp_diffs, dp_diffs) = diffs
+        .into_iter()
+        .partition(|d| d.play_side == PlaySide::Single);
+
+    SongCard {
+        id: song.id,
+        title: song.title.clone(),
+        artist: song.artist.clone(),
+        version_name: version.name.clone(),
+        sp_diffs,
+        dp_diffs,
+        bpm,
+        chart_bpms,
+        tiers,
+    }
+}
+
+/// Parses `w:<strategy>` part. Returns `None` if it is not a strategy specifier.
+fn parse_strategy(query: &str) -> Option<Result<StrategyKind, FilterQueryError>> {
+    let value = query
+        .strip_prefix("w:")
+        .or_else(|| query.strip_prefix("weight:"))?;
+    Some(
+        value
+            .parse()
+            .map_err(|_| FilterQueryError::InvalidValue(value.into())),
+    )
+}
+
+fn parse_extended_query(
+    query: &str,
+) -> Result<(Vec<FilterQuery>, usize, StrategyKind), FilterQueryError> {
+    let mut filters = vec![];
+    let mut strategy = StrategyKind::default();
+    for q in query.trim().split_ascii_whitespace() {
+        if let Some(parsed) = parse_strategy(q) {
+            strategy = parsed?;
+        } else {
+            filters.push(q.parse()?);
+        }
+    }
+
+    Ok((filters, 1, strategy))
+}
+
+/// Filters, count and the query without count parsed from one compact query
+/// (e.g. `sph10*3`, `list:practice*3`).
+type CompactQuery<'a> = (Vec<FilterQuery>, usize, &'a str);
+
+fn parse_compact_queries(
+    text: &str,
+) -> Result<(Vec<CompactQuery<'_>>, StrategyKind), FilterQueryError> {
+    let mut queries = vec![];
+    let mut strategy = StrategyKind::default();
+    for query_text in text.trim().split_ascii_whitespace() {
+        if let Some(parsed) = parse_strategy(query_text) {
+            strategy = parsed?;
+            continue;
+        }
+        if let Some(list_text) = query_text.strip_prefix("list:") {
+            let (list_name, count) = split_count(list_text);
+            let Some(count) = count else {
+                continue;
+            };
+            let target = split_count(query_text).0;
+            let (scope, list_name) = parse_list_name(list_name);
+            queries.push((
+                vec![FilterQuery::List(scope, list_name.into())],
+                count,
+                target,
+            ));
+            continue;
+        }
+        if query_text.len() <= 3 {
+            continue;
+        }
+        // non-ASCII text may have no char boundary there
+        let Some(kind_text) = query_text.get(..3) else {
+            continue;
+        };
+
+        let (play_side, difficulty) = match kind_text {
+            "spb" => (
+                FilterQuery::PlaySide(PlaySide::Single),
+                FilterQuery::Difficulty(Difficulty::Beginner),
+            ),
+            "spn" => (
+                FilterQuery::PlaySide(PlaySide::Single),
+                FilterQuery::Difficulty(Difficulty::Normal),
+            ),
+            "sph" => (
+                FilterQuery::PlaySide(PlaySide::Single),
+                FilterQuery::Difficulty(Difficulty::Hyper),
+            ),
+            "spa" => (
+                FilterQuery::PlaySide(PlaySide::Single),
+                FilterQuery::Difficulty(Difficulty::Another),
+            ),
+            "spl" => (
+                FilterQuery::PlaySide(PlaySide::Single),
+                FilterQuery::Difficulty(Difficulty::Leggendaria),
+            ),
+            "dpn" => (
+                FilterQuery::PlaySide(PlaySide::Double),
+                FilterQuery::Difficulty(Difficulty::Normal),
+            ),
+            "dph" => (
+                FilterQuery::PlaySide(PlaySide::Double),
+                FilterQuery::Difficulty(Difficulty::Hyper),
+            ),
+            "dpa" => (
+                FilterQuery::PlaySide(PlaySide::Double),
+                FilterQuery::Difficulty(Difficulty::Another),
+            ),
+            "dpl" => (
+                FilterQuery::PlaySide(PlaySide::Double),
+                FilterQuery::Difficulty(Difficulty::Leggendaria),
+            ),
+            _ => continue,
+        };
+
+        let target = split_count(query_text).0;
+        let (level, count) = split_count(&query_text[3..]);
+        let Ok(level) = level.parse::<i64>() else {
+            continue;
+        };
+        let Some(count) = count else {
+            continue;
+        };
+
+        queries.push((
+            vec![play_side, difficulty, FilterQuery::Level(level)],
+            count,
+            target,
+        ));
+    }
+    Ok((queries, strategy))
+}
+
+/// Splits `<target>*<count>`. Count is 1 if omitted, and `None` if invalid.
+fn split_count(text: &str) -> (&str, Option<usize>) {
+    match text.split_once('*') {
+        Some((target, count)) => (target, count.parse().ok()),
+        None => (text, Some(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_compact_queries, split_count};
+    use crate::{
+        db::schema::{Difficulty, FilterQuery, ListScope, PlaySide},
+        selection::StrategyKind,
+    };
+
+    #[test]
+    fn splits_count() {
+        assert_eq!(split_count("sph10"), ("sph10", Some(1)));
+        assert_eq!(split_count("sph10*3"), ("sph10", Some(3)));
+        assert_eq!(split_count("sph10*x"), ("sph10", None));
+        assert_eq!(split_count("sph10*"), ("sph10", None));
+    }
+
+    #[test]
+    fn parses_compact_queries() {
+        let (queries, strategy) =
+            parse_compact_queries(" sph10*2  dpa12 list:#practice*3 w:fresh ").unwrap();
+
+        assert_eq!(strategy, StrategyKind::InverseFrequency);
+        assert_eq!(
+            queries,
+            vec![
+                (
+                    vec![
+                        FilterQuery::PlaySide(PlaySide::Single),
+                        FilterQuery::Difficulty(Difficulty::Hyper),
+                        FilterQuery::Level(10),
+                    ],
+                    2,
+                    "sph10",
+                ),
+                (
+                    vec![
+                        FilterQuery::PlaySide(PlaySide::Double),
+                        FilterQuery::Difficulty(Difficulty::Another),
+                        FilterQuery::Level(12),
+                    ],
+                    1,
+                    "dpa12",
+                ),
+                (
+                    vec![FilterQuery::List(ListScope::Channel, "practice".into())],
+                    3,
+                    "list:#practice",
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_invalid_compact_queries() {
+        let (queries, strategy) =
+            parse_compact_queries("sph spx10 sphx sph10*x dpb5 list:p*x").unwrap();
+        assert!(queries.is_empty());
+        assert_eq!(strategy, StrategyKind::Uniform);
+
+        assert!(parse_compact_queries("w:unknown").is_err());
+    }
+
+    #[test]
+    fn skips_non_ascii_compact_queries() {
+        let (queries, _) = parse_compact_queries("ab冥 冥冥 sp冥 sph冥 spa12").unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].2, "spa12");
+    }
+}
@@ -0,0 +1,745 @@
+use crate::{
+    chat::schema::{CommandError, CommandResult, DiffLine, Notice, SongCard},
+    config::EmojiConfig,
+    db::schema::{Diff, Tier},
+    web::schema::{
+        ActionIntegration, ActionSlot, AttachmentAction, AttachmentSongField, AttachmentSongInfo,
+        DiscordEmbed, DiscordEmbedField, DiscordEmbedFooter, DiscordMessage, MattermostAction,
+        MattermostActionContext, MattermostEnqueueResult, MattermostResponseType, SlackBlock,
+        SlackMessage, SlackResponseType, SlackText,
+    },
+};
+
+use unicode_width::UnicodeWidthStr;
+
+/// Converts command results into messages of a chat platform.
+pub trait Renderer {
+    type Output;
+
+    fn render(&self, result: &CommandResult) -> Self::Output;
+}
+
+/// Markdown messages with attachments and custom emojis.
+#[derive(Debug, Clone, Copy)]
+pub struct MattermostRenderer<'a> {
+    pub username: &'a str,
+    pub emoji: &'a EmojiConfig,
+
+    /// Adds buttons for drawn diffs if specified.
+    pub actions: Option<MattermostActions<'a>>,
+}
+
+/// Where message buttons post back to.
+#[derive(Debug, Clone, Copy)]
+pub struct MattermostActions<'a> {
+    pub url: &'a str,
+
+    /// Webhook token which authorizes the actions.
+    pub token: &'a str,
+}
+
+/// Mattermost lays out buttons in rows, so larger draws don't get them.
+const MATTERMOST_MAX_ACTION_SLOTS: usize = 5;
+
+impl Renderer for MattermostRenderer<'_> {
+    type Output = MattermostEnqueueResult;
+
+    fn render(&self, result: &CommandResult) -> MattermostEnqueueResult {
+        let notices = result.notices.iter().map(|n| match n {
+            Notice::ListUpdated { name, added } => {
+                format!("Added {added} diffs to list **{name}**")
+            }
+            Notice::ListShown { name, count } => format!("List **{name}** ({count} diffs)"),
+        });
+        let diffs = result
+            .diffs
+            .iter()
+            .map(|d| format!("* {}", emoji_diff_line(self.emoji, d, "**")));
+        let texts: Vec<_> = notices.chain(diffs).collect();
+        let mut attachments: Vec<_> = result
+            .songs
+            .iter()
+            .map(|s| self.song_attachment(s))
+            .collect();
+        if let Some(actions) = &self.actions {
+            let slots_count = result.diffs.len();
+            if slots_count > 0 && slots_count <= MATTERMOST_MAX_ACTION_SLOTS {
+                attachments.push(self.actions_attachment(actions, result));
+            }
+        }
+
+        MattermostEnqueueResult {
+            response_type: None,
+            username: self.username.to_string(),
+            text: texts.join("\n"),
+            attachments,
+        }
+    }
+}
+
+impl MattermostRenderer<'_> {
+    /// Usage of the slash command, shown only to the sender.
+    pub fn help(&self, command: &str) -> MattermostEnqueueResult {
+        self.ephemeral(format!(
+            "#### Usage\n\
+            Each line of `{command} <query>` is one of:\n\
+            * `<title>`: shows the song closest to the title (e.g. `{command} fly above`)\n\
+            * `?<filters>`: draws a diff by `type:value` filters (e.g. `{command} ?p:sp l:12 w:fresh`)\n\
+            * `!<compact queries>`: draws diffs by `<sp|dp><b|n|h|a|l><level>*<count>` (e.g. `{command} !sph10*2 spa12`)\n\
+            * `list <name>`: shows the list (e.g. `{command} list practice`)\n\
+            * `+list <name> <query>`: adds diffs of the query to the list (e.g. `{command} +list practice ?l:12`)\n\
+            \n\
+            `{command} help` shows this message."
+        ))
+    }
+
+    /// Failure of the slash command, shown only to the sender.
+    pub fn error(&self, command: &str, text: &str, err: &CommandError) -> MattermostEnqueueResult {
+        self.ephemeral(format!(
+            "`{command} {text}`: {err}\nType `{command} help` for usage."
+        ))
+    }
+
+    fn ephemeral(&self, text: String) -> MattermostEnqueueResult {
+        MattermostEnqueueResult {
+            response_type: Some(MattermostResponseType::Ephemeral),
+            username: self.username.to_string(),
+            text,
+            attachments: vec![],
+        }
+    }
+
+    /// Reroll and detail buttons for each slot, carrying the whole message state.
+    fn actions_attachment(
+        &self,
+        actions: &MattermostActions,
+        result: &CommandResult,
+    ) -> AttachmentSongInfo {
+        let slots: Vec<_> = result
+            .diffs
+            .iter()
+            .map(|d| ActionSlot {
+                song_id: d.song_id,
+                play_side: d.play_side,
+                difficulty: d.difficulty,
+                redraw: d.redraw.clone(),
+            })
+            .collect();
+        let song_ids: Vec<_> = result.songs.iter().map(|s| s.id).collect();
+        let button = |action: MattermostAction, slot: usize, name: String| AttachmentAction {
+            id: format!("{action:?}{slot}").to_lowercase(),
+            name,
+            integration: ActionIntegration {
+                url: actions.url.to_string(),
+                context: MattermostActionContext {
+                    token: actions.token.to_string(),
+                    action,
+                    slot,
+                    slots: slots.clone(),
+                    song_ids: song_ids.clone(),
+                },
+            },
+        };
+
+        let mut buttons = vec![];
+        for (i, diff) in result.diffs.iter().enumerate() {
+            if diff.redraw.is_some() {
+                buttons.push(button(
+                    MattermostAction::Reroll,
+                    i,
+                    format!("Reroll #{}", i + 1),
+                ));
+            }
+            if !song_ids.contains(&diff.song_id) {
+                buttons.push(button(
+                    MattermostAction::Show,
+                    i,
+                    format!("Details #{}", i + 1),
+                ));
+            }
+        }
+
+        AttachmentSongInfo {
+            fallback: "Reroll or show details".into(),
+            title: String::new(),
+            footer: String::new(),
+            fields: vec![],
+            actions: buttons,
+        }
+    }
+
+    fn song_attachment(&self, song: &SongCard) -> AttachmentSongInfo {
+        let mut fields = vec![
+            AttachmentSongField {
+                short: true,
+                title: "SP Levels".into(),
+                value: emoji_levels(self.emoji, &song.sp_diffs),
+            },
+            AttachmentSongField {
+                short: true,
+                title: "DP Levels".into(),
+                value: emoji_levels(self.emoji, &song.dp_diffs),
+            },
+            AttachmentSongField {
+                short: true,
+                title: "BPM".into(),
+                value: emoji_bpms(self.emoji, song),
+            },
+        ];
+        if !song.tiers.is_empty() {
+            fields.push(AttachmentSongField {
+                short: true,
+                title: "Tiers".into(),
+                value: emoji_tiers(self.emoji, &song.tiers),
+            });
+        }
+
+        AttachmentSongInfo {
+            fallback: PlainTextRenderer.song_text(song),
+            title: format!("{} / {}", song.title, song.artist),
+            footer: song.version_name.clone(),
+            fields,
+            actions: vec![],
+        }
+    }
+}
+
+/// Block Kit messages with custom emojis.
+#[derive(Debug, Clone, Copy)]
+pub struct SlackRenderer<'a> {
+    pub emoji: &'a EmojiConfig,
+}
+
+impl Renderer for SlackRenderer<'_> {
+    type Output = SlackMessage;
+
+    fn render(&self, result: &CommandResult) -> SlackMessage {
+        let notices = result.notices.iter().map(|n| match n {
+            Notice::ListUpdated { name, added } => format!("Added {added} diffs to list *{name}*"),
+            Notice::ListShown { name, count } => format!("List *{name}* ({count} diffs)"),
+        });
+        let diffs = result
+            .diffs
+            .iter()
+            .map(|d| format!("• {}", emoji_diff_line(self.emoji, d, "*")));
+        let texts: Vec<_> = notices.chain(diffs).collect();
+
+        let mut blocks = vec![];
+        if !texts.is_empty() {
+            blocks.push(SlackBlock::Section {
+                text: Some(mrkdwn(texts.join("\n"))),
+                fields: vec![],
+            });
+        }
+        for song in &result.songs {
+            if !blocks.is_empty() {
+                blocks.push(SlackBlock::Divider);
+            }
+            blocks.extend(self.song_blocks(song));
+        }
+
+        let fallback = PlainTextRenderer.render(result);
+        SlackMessage {
+            response_type: SlackResponseType::InChannel,
+            text: if fallback.is_empty() {
+                "No results.".into()
+            } else {
+                fallback
+            },
+            blocks,
+        }
+    }
+}
+
+impl SlackRenderer<'_> {
+    fn song_blocks(&self, song: &SongCard) -> Vec<SlackBlock> {
+        let mut fields = vec![
+            mrkdwn(format!(
+                "*SP Levels*\n{}",
+                emoji_levels(self.emoji, &song.sp_diffs)
+            )),
+            mrkdwn(format!(
+                "*DP Levels*\n{}",
+                emoji_levels(self.emoji, &song.dp_diffs)
+            )),
+            mrkdwn(format!("*BPM*\n{}", emoji_bpms(self.emoji, song))),
+        ];
+        if !song.tiers.is_empty() {
+            fields.push(mrkdwn(format!(
+                "*Tiers*\n{}",
+                emoji_tiers(self.emoji, &song.tiers)
+            )));
+        }
+
+        vec![
+            SlackBlock::Section {
+                text: Some(mrkdwn(format!("*{}* / {}", song.title, song.artist))),
+                fields,
+            },
+            SlackBlock::Context {
+                elements: vec![mrkdwn(song.version_name.clone())],
+            },
+        ]
+    }
+}
+
+fn mrkdwn(text: String) -> SlackText {
+    SlackText { text }
+}
+
+/// Markdown content with embeds. Custom emojis are not used since they are per guild.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiscordRenderer;
+
+/// Discord rejects messages with more embeds.
+const DISCORD_MAX_EMBEDS: usize = 10;
+
+impl Renderer for DiscordRenderer {
+    type Output = DiscordMessage;
+
+    fn render(&self, result: &CommandResult) -> DiscordMessage {
+        let notices = result.notices.iter().map(|n| match n {
+            Notice::ListUpdated { name, added } => {
+                format!("Added {added} diffs to list **{name}**")
+            }
+            Notice::ListShown { name, count } => format!("List **{name}** ({count} diffs)"),
+        });
+        let diffs = result.diffs.iter().map(|d| {
+            format!(
+                "* [{} {} {}] **{}** ({})",
+                d.play_side, d.difficulty, d.level, d.title, d.version_abbrev
+            )
+        });
+        let texts: Vec<_> = notices.chain(diffs).collect();
+        let embeds: Vec<_> = result
+            .songs
+            .iter()
+            .take(DISCORD_MAX_EMBEDS)
+            .map(|s| self.song_embed(s))
+            .collect();
+
+        DiscordMessage {
+            content: if texts.is_empty() && embeds.is_empty() {
+                "No results.".into()
+            } else {
+                texts.join("\n")
+            },
+            embeds,
+            flags: None,
+        }
+    }
+}
+
+impl DiscordRenderer {
+    fn song_embed(&self, song: &SongCard) -> DiscordEmbed {
+        let plain = PlainTextRenderer;
+        let tiers: Vec<_> = song
+            .tiers
+            .iter()
+            .map(|t| {
+                format!(
+                    "{} {} {}: {}",
+                    t.play_side, t.difficulty, t.table_abbrev, t.tier
+                )
+            })
+            .collect();
+        let mut fields = vec![
+            discord_field("SP Levels", plain.levels(&song.sp_diffs)),
+            discord_field("DP Levels", plain.levels(&song.dp_diffs)),
+            discord_field("BPM", plain.bpms(song)),
+        ];
+        if !tiers.is_empty() {
+            fields.push(discord_field("Tiers", tiers.join(" / ")));
+        }
+
+        DiscordEmbed {
+            title: format!("{} / {}", song.title, song.artist),
+            fields,
+            footer: DiscordEmbedFooter {
+                text: song.version_name.clone(),
+            },
+        }
+    }
+}
+
+/// Inline field. Discord rejects empty values, so they are replaced.
+fn discord_field(name: &str, value: String) -> DiscordEmbedField {
+    DiscordEmbedField {
+        name: name.into(),
+        value: if value.is_empty() { "-".into() } else { value },
+        inline: true,
+    }
+}
+
+/// Text without any markup, such as for terminals.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainTextRenderer;
+
+impl Renderer for PlainTextRenderer {
+    type Output = String;
+
+    fn render(&self, result: &CommandResult) -> String {
+        let mut lines = vec![];
+        for notice in &result.notices {
+            lines.push(match notice {
+                Notice::ListUpdated { name, added } => {
+                    format!("Added {added} diffs to list {name}")
+                }
+                Notice::ListShown { name, count } => format!("List {name} ({count} diffs)"),
+            });
+        }
+        for diff in &result.diffs {
+            lines.push(format!(
+                "* [{} {} {}] {} ({})",
+                diff.play_side, diff.difficulty, diff.level, diff.title, diff.version_abbrev
+            ));
+        }
+        for song in &result.songs {
+            lines.push(self.song_text(song));
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl PlainTextRenderer {
+    pub fn song_text(&self, song: &SongCard) -> String {
+        let mut lines = vec![
+            format!("{} / {} ({})", song.title, song.artist, song.version_name),
+            format!("  SP: {}", self.levels(&song.sp_diffs)),
+            format!("  DP: {}", self.levels(&song.dp_diffs)),
+            format!("  BPM: {}", self.bpms(song)),
+        ];
+        if !song.tiers.is_empty() {
+            let tier_texts: Vec<_> = song
+                .tiers
+                .iter()
+                .map(|t| {
+                    format!(
+                        "{} {} {}: {}",
+                        t.play_side, t.difficulty, t.table_abbrev, t.tier
+                    )
+                })
+                .collect();
+            lines.push(format!("  Tiers: {}", tier_texts.join(" / ")));
+        }
+
+        lines.join("\n")
+    }
+
+    fn levels(&self, diffs: &[Diff]) -> String {
+        let texts: Vec<_> = diffs
+            .iter()
+            .map(|d| match d.notes {
+                Some(notes) => format!("{} {} ({notes})", d.difficulty, d.level),
+                None => format!("{} {}", d.difficulty, d.level),
+            })
+            .collect();
+        texts.join(" / ")
+    }
+
+    /// Song tempo followed by charts with their own tempo.
+    fn bpms(&self, song: &SongCard) -> String {
+        let chart_bpms = song
+            .chart_bpms
+            .iter()
+            .map(|(play_side, difficulty, bpm)| format!("{play_side} {difficulty} {bpm}"));
+        let texts: Vec<_> = [song.bpm.to_string()]
+            .into_iter()
+            .chain(chart_bpms)
+            .collect();
+        texts.join(" / ")
+    }
+}
+
+/// Aligned columns for terminals.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableRenderer;
+
+impl Renderer for TableRenderer {
+    type Output = String;
+
+    fn render(&self, result: &CommandResult) -> String {
+        let mut sections = vec![];
+        for notice in &result.notices {
+            sections.push(match notice {
+                Notice::ListUpdated { name, added } => {
+                    format!("Added {added} diffs to list {name}\n")
+                }
+                Notice::ListShown { name, count } => format!("List {name} ({count} diffs)\n"),
+            });
+        }
+        if !result.diffs.is_empty() {
+            let rows = result
+                .diffs
+                .iter()
+                .map(|d| {
+                    vec![
+                        d.play_side.to_string(),
+                        d.difficulty.to_string(),
+                        d.level.to_string(),
+                        d.title.clone(),
+                        d.version_abbrev.clone(),
+                    ]
+                })
+                .collect();
+            sections.push(table(
+                &["PLAY", "DIFFICULTY", "LEVEL", "TITLE", "VERSION"],
+                rows,
+            ));
+        }
+        if !result.songs.is_empty() {
+            let levels = |diffs: &[Diff]| {
+                let texts: Vec<_> = diffs
+                    .iter()
+                    .map(|d| format!("{}{}", &d.difficulty.to_string()[..1], d.level))
+                    .collect();
+                texts.join(" ")
+            };
+            let rows = result
+                .songs
+                .iter()
+                .map(|s| {
+                    vec![
+                        s.id.to_string(),
+                        s.title.clone(),
+                        s.artist.clone(),
+                        s.version_name.clone(),
+                        levels(&s.sp_diffs),
+                        levels(&s.dp_diffs),
+                        s.bpm.to_string(),
+                    ]
+                })
+                .collect();
+            sections.push(table(
+                &["ID", "TITLE", "ARTIST", "VERSION", "SP", "DP", "BPM"],
+                rows,
+            ));
+        }
+
+        sections.join("\n")
+    }
+}
+
+/// Pads cells by their display width, since titles contain wide characters.
+fn table(header: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<_> = header.iter().map(|h| h.width()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    let header_row = header.iter().map(|h| h.to_string()).collect();
+    let mut lines = String::new();
+    for row in [header_row].into_iter().chain(rows) {
+        let cells: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell}{}", " ".repeat(width - cell.width())))
+            .collect();
+        lines.push_str(cells.join("  ").trim_end());
+        lines.push('\n');
+    }
+    lines
+}
+
+/// `[SP <difficulty> <level>] <title> (<version>)` with the title wrapped in `strong`.
+fn emoji_diff_line(emoji: &EmojiConfig, diff: &DiffLine, strong: &str) -> String {
+    format!(
+        "[{} {} {}] {strong}{}{strong} ({})",
+        diff.play_side,
+        emoji.difficulty(diff.difficulty),
+        emoji.level(diff.level),
+        diff.title,
+        diff.version_abbrev
+    )
+}
+
+fn emoji_levels(emoji: &EmojiConfig, diffs: &[Diff]) -> String {
+    let texts: Vec<_> = diffs
+        .iter()
+        .map(|d| {
+            let difficulty = emoji.difficulty(d.difficulty);
+            let level = emoji.level(d.level);
+            match d.notes {
+                Some(notes) => format!("{difficulty} {level} ({notes})"),
+                None => format!("{difficulty} {level}"),
+            }
+        })
+        .collect();
+    texts.join(" / ")
+}
+
+/// Song tempo followed by charts with their own tempo.
+fn emoji_bpms(emoji: &EmojiConfig, song: &SongCard) -> String {
+    let chart_bpms = song.chart_bpms.iter().map(|(play_side, difficulty, bpm)| {
+        format!("{play_side} {} {bpm}", emoji.difficulty(*difficulty))
+    });
+    let texts: Vec<_> = [song.bpm.to_string()]
+        .into_iter()
+        .chain(chart_bpms)
+        .collect();
+    texts.join(" / ")
+}
+
+fn emoji_tiers(emoji: &EmojiConfig, tiers: &[Tier]) -> String {
+    let texts: Vec<_> = tiers
+        .iter()
+        .map(|t| {
+            format!(
+                "{} {} {}: {}",
+                t.play_side,
+                emoji.difficulty(t.difficulty),
+                t.table_abbrev,
+                t.tier
+            )
+        })
+        .collect();
+    texts.join(" / ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DiscordRenderer, MattermostActions, MattermostRenderer, PlainTextRenderer, Renderer,
+        SlackRenderer, TableRenderer,
+    };
+    use crate::{
+        chat::schema::{BpmRange, CommandError, CommandResult, DiffLine, Notice, SongCard},
+        config::EmojiConfig,
+        db::schema::{Diff, Difficulty, PlaySide, Tier},
+    };
+
+    fn diff(play_side: PlaySide, difficulty: Difficulty, level: i64) -> Diff {
+        Diff {
+            song_id: 1,
+            play_side,
+            difficulty,
+            level,
+            notes: Some(1000 + level * 50),
+            min_bpm: None,
+            max_bpm: Some(150),
+            bpm_indeterminate: false,
+            added_version_id: None,
+            note_type: None,
+            scratch_type: None,
+        }
+    }
+
+    fn sample_result() -> CommandResult {
+        CommandResult {
+            notices: vec![Notice::ListUpdated {
+                name: "practice".into(),
+                added: 2,
+            }],
+            diffs: vec![
+                DiffLine {
+                    song_id: 1,
+                    redraw: Some("?l:12".into()),
+                    play_side: PlaySide::Single,
+                    difficulty: Difficulty::Another,
+                    level: 12,
+                    title: "冥".into(),
+                    version_abbrev: "7th".into(),
+                },
+                DiffLine {
+                    song_id: 2,
+                    redraw: None,
+                    play_side: PlaySide::Double,
+                    difficulty: Difficulty::Hyper,
+                    level: 10,
+                    title: "Fly Above".into(),
+                    version_abbrev: "RESIDENT".into(),
+                },
+            ],
+            songs: vec![SongCard {
+                id: 1,
+                title: "冥".into(),
+                artist: "Zektbach".into(),
+                version_name: "beatmania IIDX 7th style".into(),
+                sp_diffs: vec![
+                    diff(PlaySide::Single, Difficulty::Normal, 5),
+                    diff(PlaySide::Single, Difficulty::Hyper, 10),
+                    diff(PlaySide::Single, Difficulty::Another, 12),
+                ],
+                dp_diffs: vec![],
+                bpm: BpmRange {
+                    min: None,
+                    max: Some(150),
+                },
+                chart_bpms: vec![(
+                    PlaySide::Single,
+                    Difficulty::Another,
+                    BpmRange {
+                        min: Some(75),
+                        max: Some(300),
+                    },
+                )],
+                tiers: vec![Tier {
+                    song_id: 1,
+                    play_side: PlaySide::Single,
+                    difficulty: Difficulty::Another,
+                    table_abbrev: "hc".into(),
+                    table_name: "SP12 hard clear".into(),
+                    tier: "S+".into(),
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn renders_mattermost() {
+        let emoji = EmojiConfig::default();
+        let renderer = MattermostRenderer {
+            username: "Cathedral",
+            emoji: &emoji,
+            actions: Some(MattermostActions {
+                url: "https://cathedral.example.com/mattermost/action",
+                token: "token",
+            }),
+        };
+        let message = renderer.render(&sample_result());
+        insta::assert_snapshot!(serde_json::to_string_pretty(&message).unwrap());
+    }
+
+    #[test]
+    fn renders_mattermost_help_and_error() {
+        let emoji = EmojiConfig::default();
+        let renderer = MattermostRenderer {
+            username: "Cathedral",
+            emoji: &emoji,
+            actions: None,
+        };
+        let err = CommandError::NotFound("list practice".into());
+        let messages = [
+            renderer.help("/iidx"),
+            renderer.error("/iidx", "list practice", &err),
+        ];
+        insta::assert_snapshot!(serde_json::to_string_pretty(&messages).unwrap());
+    }
+
+    #[test]
+    fn renders_slack() {
+        let emoji = EmojiConfig::default();
+        let renderer = SlackRenderer { emoji: &emoji };
+        let message = renderer.render(&sample_result());
+        insta::assert_snapshot!(serde_json::to_string_pretty(&message).unwrap());
+    }
+
+    #[test]
+    fn renders_discord() {
+        let message = DiscordRenderer.render(&sample_result());
+        insta::assert_snapshot!(serde_json::to_string_pretty(&message).unwrap());
+    }
+
+    #[test]
+    fn renders_plain_text() {
+        insta::assert_snapshot!(PlainTextRenderer.render(&sample_result()));
+    }
+
+    #[test]
+    fn renders_table() {
+        insta::assert_snapshot!(TableRenderer.render(&sample_result()));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{
+    auth::BotCommand,
+    db::schema::{Diff, Difficulty, FilterContext, FilterQueryError, PlaySide, Tier},
+};
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use serde::Serialize;
+use sqlx::Error as SqlxError;
+use thiserror::Error as ThisError;
+
+/// Who sent a chat command, and where.
+#[derive(Debug, Clone, Copy)]
+pub struct CommandContext<'a> {
+    pub user_name: &'a str,
+    pub user_id: &'a str,
+    pub channel_id: &'a str,
+
+    /// Command kinds the sender is allowed to run.
+    pub commands: &'a [BotCommand],
+}
+
+impl<'a> CommandContext<'a> {
+    pub fn filter_context(&self) -> FilterContext<'a> {
+        FilterContext {
+            user_name: self.user_name,
+            channel_id: self.channel_id,
+        }
+    }
+}
+
+#[derive(Debug, ThisError)]
+pub enum CommandError {
+    #[error("db error: {0}")]
+    Sqlx(#[from] SqlxError),
+
+    #[error("filter error: {0}")]
+    FilterQuery(#[from] FilterQueryError),
+
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    #[error("forbidden: {0:?} command")]
+    Forbidden(BotCommand),
+}
+
+/// Result of chat command lines, independent of chat platforms.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommandResult {
+    pub notices: Vec<Notice>,
+
+    /// Diffs drawn or stored in lists.
+    pub diffs: Vec<DiffLine>,
+
+    /// Songs found by titles.
+    pub songs: Vec<SongCard>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notice {
+    /// Diffs are added to the list.
+    ListUpdated { name: String, added: usize },
+
+    /// Diffs in the list follow.
+    ListShown { name: String, count: usize },
+}
+
+/// A diff with its song.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffLine {
+    pub song_id: i64,
+
+    /// Query line which draws another diff in place of this. `None` if not drawn.
+    pub redraw: Option<String>,
+    pub play_side: PlaySide,
+    pub difficulty: Difficulty,
+    pub level: i64,
+    pub title: String,
+    pub version_abbrev: String,
+}
+
+/// A song with its diffs and tiers.
+#[derive(Debug, Clone, Serialize)]
+pub struct SongCard {
+    pub id: i64,
+    pub title: String,
+    pub artist: String,
+    pub version_name: String,
+    pub sp_diffs: Vec<Diff>,
+    pub dp_diffs: Vec<Diff>,
+    pub bpm: BpmRange,
+
+    /// Charts whose tempo differs from the song's one.
+    pub chart_bpms: Vec<(PlaySide, Difficulty, BpmRange)>,
+    pub tiers: Vec<Tier>,
+}
+
+/// Tempo shown in messages. `max` is `None` if indeterminate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BpmRange {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+}
+
+impl Display for BpmRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match (self.min, self.max) {
+            (Some(min_bpm), Some(max_bpm)) => write!(f, "{min_bpm} - {max_bpm}"),
+            (None, Some(max_bpm)) => write!(f, "{max_bpm}"),
+            (_, None) => f.write_str("※"),
+        }
+    }
+}
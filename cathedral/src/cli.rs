@@ -1,14 +1,119 @@
 use std::{net::SocketAddr, path::PathBuf};
 
-use clap::Parser;
+use crate::selection::{HistoryMode, HistoryScope};
 
-#[derive(Debug, Parser)]
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Debug, Clone, Parser)]
 pub struct Arguments {
-    pub sqlite_filename: PathBuf,
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Runs the HTTP server.
+    Serve(ConfigArguments),
+
+    /// Runs chat queries against the catalog and prints the results.
+    Query(QueryArguments),
+}
+
+/// Files read by every subcommand. Each overrides the corresponding item of the config file.
+#[derive(Debug, Clone, Default, Parser)]
+pub struct SourceArguments {
+    /// SQLite file of the song catalog.
+    #[clap(env = "CATHEDRAL_CATALOG_FILENAME")]
+    pub sqlite_filename: Option<PathBuf>,
+
+    /// TOML config file.
+    #[clap(short, long, env = "CATHEDRAL_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Writable SQLite file for user data. Kept in memory if omitted.
+    #[clap(short, long, env = "CATHEDRAL_USERDATA_FILENAME")]
+    pub userdata_filename: Option<PathBuf>,
+}
+
+/// Every option overrides the corresponding item of the config file.
+#[derive(Debug, Clone, Default, Parser)]
+pub struct ConfigArguments {
+    #[clap(flatten)]
+    pub source: SourceArguments,
+
+    #[clap(short, long, env = "CATHEDRAL_BIND")]
+    pub bind: Option<SocketAddr>,
+
+    #[clap(short, long, env = "CATHEDRAL_MATTERMOST_TOKEN")]
+    pub mattermost_token: Option<String>,
+
+    /// Number of recent draws per channel or user considered as history.
+    #[clap(long, env = "CATHEDRAL_HISTORY_WINDOW")]
+    pub history_window: Option<usize>,
+
+    #[clap(long, value_enum, env = "CATHEDRAL_HISTORY_MODE")]
+    pub history_mode: Option<HistoryMode>,
+
+    #[clap(long, value_enum, env = "CATHEDRAL_HISTORY_SCOPE")]
+    pub history_scope: Option<HistoryScope>,
 
-    #[clap(short, long, default_value = "0.0.0.0:3000")]
-    pub bind: SocketAddr,
+    /// Number of songs returned by search when `limit` is omitted.
+    #[clap(long, env = "CATHEDRAL_SEARCH_LIMIT")]
+    pub search_limit: Option<usize>,
+
+    /// Maximum number of items in a page of search or listing results.
+    #[clap(long, env = "CATHEDRAL_MAX_RESULTS")]
+    pub max_results: Option<usize>,
+
+    /// Accepts empty webhook tokens.
+    #[clap(long, env = "CATHEDRAL_ALLOW_EMPTY_TOKEN")]
+    pub allow_empty_token: bool,
+
+    /// Bearer token for admin endpoints and user data updates. They are disabled if empty.
+    #[clap(long, env = "CATHEDRAL_ADMIN_TOKEN")]
+    pub admin_token: Option<String>,
+
+    /// Seconds between checks of the catalog file for reloading. Disabled if 0.
+    #[clap(long, env = "CATHEDRAL_RELOAD_INTERVAL")]
+    pub reload_interval: Option<u64>,
+
+    /// Username of the bot in chat.
+    #[clap(long, env = "CATHEDRAL_BOT_USERNAME")]
+    pub bot_username: Option<String>,
+
+    /// Public URL of `/mattermost/action` for message buttons.
+    #[clap(long, env = "CATHEDRAL_ACTION_URL")]
+    pub action_url: Option<String>,
+
+    /// Log level.
+    #[clap(long, env = "CATHEDRAL_LOG_LEVEL")]
+    pub log_level: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct QueryArguments {
+    /// Other settings are read from the config file only.
+    #[clap(flatten)]
+    pub source: SourceArguments,
+
+    /// Query line in the chat syntax. Read from stdin if omitted.
+    #[clap(short, long = "query")]
+    pub queries: Vec<String>,
+
+    #[clap(long, value_enum, default_value = "table")]
+    pub format: OutputFormat,
+
+    /// User name which user lists belong to.
+    #[clap(long, default_value = "cli")]
+    pub user: String,
+
+    /// Channel name which draw history belongs to.
+    #[clap(long, default_value = "cli")]
+    pub channel: String,
+}
 
-    #[clap(short, long, default_value = "")]
-    pub mattermost_token: String,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
 }
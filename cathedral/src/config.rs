@@ -0,0 +1,479 @@
+use crate::{
+    auth::BotCommand,
+    cli::ConfigArguments,
+    db::schema::Difficulty,
+    selection::{HistoryMode, HistoryScope},
+    web::schema::MattermostResponseType,
+};
+
+use std::{
+    collections::HashSet,
+    fs::read_to_string,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use ed25519_dalek::{VerifyingKey, PUBLIC_KEY_LENGTH};
+use lyricism::CostProfile;
+use serde::Deserialize;
+use tracing::level_filters::LevelFilter;
+
+const LEVEL_PLACEHOLDER: &str = "{level}";
+
+/// Server settings. Read from the TOML file, then overridden by environment variables and arguments.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub search: SearchConfig,
+    pub history: HistoryConfig,
+    pub bot: BotConfig,
+    pub tokens: TokensConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// SQLite file of the song catalog.
+    pub catalog_filename: Option<PathBuf>,
+    pub bind: SocketAddr,
+
+    /// Writable SQLite file for user data. Kept in memory if omitted.
+    pub userdata_filename: Option<PathBuf>,
+
+    /// Seconds between checks of the catalog file for reloading. Disabled if 0.
+    pub reload_interval: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            catalog_filename: None,
+            bind: (Ipv4Addr::UNSPECIFIED, 3000).into(),
+            userdata_filename: None,
+            reload_interval: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Number of songs returned by search when `limit` is omitted.
+    pub limit: usize,
+
+    /// Maximum number of items in a page of search or listing results.
+    pub max_results: usize,
+
+    /// Edit costs for song title search.
+    pub costs: CostProfile,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            limit: 5,
+            max_results: 100,
+            costs: CostProfile::DEFAULT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Number of recent draws per channel or user considered as history.
+    pub window: usize,
+    pub mode: HistoryMode,
+    pub scope: HistoryScope,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> HistoryConfig {
+        HistoryConfig {
+            window: 30,
+            mode: HistoryMode::default(),
+            scope: HistoryScope::default(),
+        }
+    }
+}
+
+/// How the bot appears in chat.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    pub username: String,
+    pub emoji: EmojiConfig,
+
+    /// Public URL of `/mattermost/action`. Messages get reroll and detail buttons if specified.
+    pub action_url: Option<String>,
+}
+
+impl Default for BotConfig {
+    fn default() -> BotConfig {
+        BotConfig {
+            username: "Cathedral".into(),
+            emoji: EmojiConfig::default(),
+            action_url: None,
+        }
+    }
+}
+
+/// Custom emoji names. `level` has `{level}` replaced with the level number.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmojiConfig {
+    pub beginner: String,
+    pub normal: String,
+    pub hyper: String,
+    pub another: String,
+    pub leggendaria: String,
+    pub level: String,
+}
+
+impl Default for EmojiConfig {
+    fn default() -> EmojiConfig {
+        EmojiConfig {
+            beginner: ":diff-b:".into(),
+            normal: ":diff-n:".into(),
+            hyper: ":diff-h:".into(),
+            another: ":diff-a:".into(),
+            leggendaria: ":diff-l:".into(),
+            level: ":level-{level}:".into(),
+        }
+    }
+}
+
+impl EmojiConfig {
+    pub fn difficulty(&self, difficulty: Difficulty) -> &str {
+        match difficulty {
+            Difficulty::Beginner => &self.beginner,
+            Difficulty::Normal => &self.normal,
+            Difficulty::Hyper => &self.hyper,
+            Difficulty::Another => &self.another,
+            Difficulty::Leggendaria => &self.leggendaria,
+        }
+    }
+
+    pub fn level(&self, level: i64) -> String {
+        self.level.replace(LEVEL_PLACEHOLDER, &level.to_string())
+    }
+}
+
+/// Tokens of integrations. Reloaded on SIGHUP and `POST /admin/reload`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokensConfig {
+    /// Registered as a webhook named `default` which runs every command in any channel.
+    pub mattermost: Option<String>,
+
+    /// Bearer token for admin endpoints and user data updates. They are disabled if empty.
+    pub admin: String,
+
+    /// Accepts empty webhook tokens. Requests without tokens pass then.
+    pub allow_empty: bool,
+
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Slack app for `/slack/command` and `/slack/events`. Disabled if omitted.
+    pub slack: Option<SlackConfig>,
+
+    /// Discord application for `/discord/interactions`. Disabled if omitted.
+    pub discord: Option<DiscordConfig>,
+}
+
+/// Outgoing webhook or slash command of a Mattermost team or channels.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub name: String,
+
+    /// Accepted tokens. Keep the old one with the new one while rotating.
+    pub tokens: Vec<String>,
+
+    /// Accepts only the team if specified.
+    pub team_id: Option<String>,
+
+    /// Accepts only these channels if not empty.
+    pub channel_ids: Vec<String>,
+
+    pub commands: Vec<BotCommand>,
+
+    /// Visibility of slash command results. Help and errors are always ephemeral.
+    pub response_type: MattermostResponseType,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> WebhookConfig {
+        WebhookConfig {
+            name: String::new(),
+            tokens: vec![],
+            team_id: None,
+            channel_ids: vec![],
+            commands: BotCommand::ALL.to_vec(),
+            response_type: MattermostResponseType::InChannel,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn accepts_channel(&self, team_id: &str, channel_id: &str) -> bool {
+        let team_accepted = match &self.team_id {
+            Some(t) => t == team_id,
+            None => true,
+        };
+        let channel_accepted =
+            self.channel_ids.is_empty() || self.channel_ids.iter().any(|c| c == channel_id);
+        team_accepted && channel_accepted
+    }
+}
+
+/// Slack app whose requests are signed with its signing secret.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlackConfig {
+    /// Accepted signing secrets. Keep the old one with the new one while rotating.
+    pub signing_secrets: Vec<String>,
+
+    /// Seconds of difference from the request timestamp allowed, against replay attacks.
+    pub timestamp_tolerance: u64,
+
+    /// Accepts only the workspace if specified.
+    pub team_id: Option<String>,
+
+    pub commands: Vec<BotCommand>,
+
+    /// Bot user OAuth token to reply to mentions with. Mentions are ignored if omitted.
+    pub bot_token: Option<String>,
+
+    /// Base URL of the Web API which replies are posted to.
+    pub api_url: String,
+}
+
+impl Default for SlackConfig {
+    fn default() -> SlackConfig {
+        SlackConfig {
+            signing_secrets: vec![],
+            timestamp_tolerance: 300,
+            team_id: None,
+            commands: BotCommand::ALL.to_vec(),
+            bot_token: None,
+            api_url: "https://slack.com/api".into(),
+        }
+    }
+}
+
+/// Discord application whose interactions are signed with its key pair.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    /// Public key of the application in hex.
+    pub public_key: String,
+
+    /// Seconds of difference from the request timestamp allowed, against replay attacks.
+    pub timestamp_tolerance: u64,
+
+    pub commands: Vec<BotCommand>,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> DiscordConfig {
+        DiscordConfig {
+            public_key: String::new(),
+            timestamp_tolerance: 300,
+            commands: BotCommand::ALL.to_vec(),
+        }
+    }
+}
+
+impl DiscordConfig {
+    pub fn verifying_key(&self) -> Result<VerifyingKey> {
+        let bytes: [u8; PUBLIC_KEY_LENGTH] = hex::decode(&self.public_key)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .context("tokens.discord.public_key: must be 32 bytes in hex")?;
+        VerifyingKey::from_bytes(&bytes).context("tokens.discord.public_key: invalid key")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// One of `off`, `error`, `warn`, `info`, `debug` and `trace`.
+    pub level: String,
+    pub ansi: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> LoggingConfig {
+        LoggingConfig {
+            level: "info".into(),
+            ansi: true,
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn level_filter(&self) -> Result<LevelFilter> {
+        LevelFilter::from_str(&self.level)
+            .with_context(|| format!("logging.level: unknown level \"{}\"", self.level))
+    }
+}
+
+impl Config {
+    /// Reads the config file if specified, applies arguments and validates the result.
+    pub fn load(args: ConfigArguments) -> Result<Config> {
+        let mut config = match &args.source.config {
+            Some(path) => {
+                let source = read_to_string(path)
+                    .with_context(|| format!("failed to read config file {}", path.display()))?;
+                toml::from_str(&source)
+                    .with_context(|| format!("failed to parse config file {}", path.display()))?
+            }
+            None => Config::default(),
+        };
+        config.apply_arguments(args);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Names of sections which differ from the other config, except `tokens`.
+    /// They are read only at startup, so reloading does not apply them.
+    pub fn changed_static_sections(&self, other: &Config) -> Vec<&'static str> {
+        let changes = [
+            ("server", self.server != other.server),
+            ("search", self.search != other.search),
+            ("history", self.history != other.history),
+            ("bot", self.bot != other.bot),
+            ("logging", self.logging != other.logging),
+        ];
+        changes
+            .into_iter()
+            .filter_map(|(name, changed)| changed.then_some(name))
+            .collect()
+    }
+
+    /// Overrides with arguments. Environment variables are handled as arguments by clap.
+    fn apply_arguments(&mut self, args: ConfigArguments) {
+        if args.source.sqlite_filename.is_some() {
+            self.server.catalog_filename = args.source.sqlite_filename;
+        }
+        if args.source.userdata_filename.is_some() {
+            self.server.userdata_filename = args.source.userdata_filename;
+        }
+        self.server.bind = args.bind.unwrap_or(self.server.bind);
+        self.server.reload_interval = args.reload_interval.unwrap_or(self.server.reload_interval);
+        self.search.limit = args.search_limit.unwrap_or(self.search.limit);
+        self.search.max_results = args.max_results.unwrap_or(self.search.max_results);
+        self.history.window = args.history_window.unwrap_or(self.history.window);
+        self.history.mode = args.history_mode.unwrap_or(self.history.mode);
+        self.history.scope = args.history_scope.unwrap_or(self.history.scope);
+        if let Some(username) = args.bot_username {
+            self.bot.username = username;
+        }
+        if args.action_url.is_some() {
+            self.bot.action_url = args.action_url;
+        }
+        if args.mattermost_token.is_some() {
+            self.tokens.mattermost = args.mattermost_token;
+        }
+        if let Some(token) = args.admin_token {
+            self.tokens.admin = token;
+        }
+        self.tokens.allow_empty |= args.allow_empty_token;
+        if let Some(level) = args.log_level {
+            self.logging.level = level;
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.server.catalog_filename.is_none() {
+            bail!("server.catalog_filename: catalog file is not specified");
+        }
+        if self.search.max_results == 0 {
+            bail!("search.max_results: must be greater than 0");
+        }
+        if self.search.limit == 0 || self.search.limit > self.search.max_results {
+            bail!(
+                "search.limit: must be between 1 and search.max_results ({}), but got {}",
+                self.search.max_results,
+                self.search.limit
+            );
+        }
+        if self.search.costs.substring_position_divisor == 0 {
+            bail!("search.costs.substring_position_divisor: must be greater than 0");
+        }
+        if self.bot.username.trim().is_empty() {
+            bail!("bot.username: must not be empty");
+        }
+        if !self.bot.emoji.level.contains(LEVEL_PLACEHOLDER) {
+            bail!("bot.emoji.level: must contain {LEVEL_PLACEHOLDER}");
+        }
+        self.logging.level_filter()?;
+        self.tokens.validate()?;
+
+        Ok(())
+    }
+}
+
+impl TokensConfig {
+    fn validate(&self) -> Result<()> {
+        if self.mattermost.as_deref() == Some("") && !self.allow_empty {
+            bail!("tokens.mattermost: must not be empty unless tokens.allow_empty is set");
+        }
+
+        let mut names = HashSet::new();
+        for webhook in &self.webhooks {
+            if webhook.name.is_empty() {
+                bail!("tokens.webhooks: every webhook must have a name");
+            }
+            if webhook.name == "default" {
+                bail!("tokens.webhooks: name \"default\" is reserved for tokens.mattermost");
+            }
+            if !names.insert(&webhook.name) {
+                bail!("tokens.webhooks: name \"{}\" is duplicated", webhook.name);
+            }
+            if webhook.tokens.is_empty() {
+                bail!("tokens.webhooks.{}: no tokens are specified", webhook.name);
+            }
+            if webhook.tokens.iter().any(|t| t.is_empty()) && !self.allow_empty {
+                bail!(
+                    "tokens.webhooks.{}: tokens must not be empty unless tokens.allow_empty is set",
+                    webhook.name
+                );
+            }
+            if webhook.commands.is_empty() {
+                bail!(
+                    "tokens.webhooks.{}: at least one command must be allowed",
+                    webhook.name
+                );
+            }
+        }
+
+        if let Some(slack) = &self.slack {
+            if slack.signing_secrets.is_empty()
+                || slack.signing_secrets.iter().any(|s| s.is_empty())
+            {
+                bail!("tokens.slack.signing_secrets: must have one or more non-empty secrets");
+            }
+            if slack.commands.is_empty() {
+                bail!("tokens.slack: at least one command must be allowed");
+            }
+            if slack.bot_token.as_ref().is_some_and(|t| t.is_empty()) {
+                bail!("tokens.slack.bot_token: must not be empty if specified");
+            }
+        }
+        if let Some(discord) = &self.discord {
+            discord.verifying_key()?;
+            if discord.commands.is_empty() {
+                bail!("tokens.discord: at least one command must be allowed");
+            }
+        }
+
+        Ok(())
+    }
+}
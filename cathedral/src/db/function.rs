@@ -1,15 +1,35 @@
-use crate::db::schema::{Diff, Difficulty, FilterQuery, PlaySide, Song, Version};
+use crate::db::schema::{
+    Availability, Diff, DiffCandidate, Difficulty, FilterContext, FilterQuery, PlaySide, Song,
+    SongSort, SortOrder, StatsCount, StatsGroup, Tier, Version,
+};
 
-use std::{iter::repeat, path::Path};
+use std::{path::Path, sync::Arc};
 
-use sqlx::{FromRow, Result as SqlxResult, SqlitePool};
+use sqlx::{
+    query::QueryAs,
+    sqlite::{SqliteArguments, SqlitePoolOptions},
+    FromRow, Result as SqlxResult, Sqlite, SqlitePool,
+};
 
-pub async fn open_sqlite_file(path: &Path) -> SqlxResult<SqlitePool> {
-    let conn = SqlitePool::connect(&format!(
-        "sqlite://{}?mode=ro",
-        path.to_str().expect("invalid filename")
-    ))
-    .await?;
+/// Opens the catalog as read-only, attaching the user data store as `userdata`.
+pub async fn open_sqlite_file(path: &Path, userdata_uri: &str) -> SqlxResult<SqlitePool> {
+    let userdata_uri: Arc<str> = userdata_uri.into();
+    let conn = SqlitePoolOptions::new()
+        .after_connect(move |conn, _| {
+            let userdata_uri = userdata_uri.clone();
+            Box::pin(async move {
+                sqlx::query("ATTACH DATABASE ? AS userdata;")
+                    .bind(&*userdata_uri)
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            })
+        })
+        .connect(&format!(
+            "sqlite://{}?mode=ro",
+            path.to_str().expect("invalid filename")
+        ))
+        .await?;
     Ok(conn)
 }
 
@@ -27,6 +47,20 @@ pub async fn fetch_title_pair(pool: &SqlitePool) -> SqlxResult<Vec<(i64, String)
     Ok(rows)
 }
 
+pub async fn fetch_alias_pairs(pool: &SqlitePool) -> SqlxResult<Vec<(i64, String)>> {
+    let rows = sqlx::query_as(
+        r#"
+        SELECT song_aliases.song_id, song_aliases.alias
+        FROM song_aliases
+        ORDER BY song_aliases.song_id;
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
 pub async fn fetch_songs_with_versions(
     pool: &SqlitePool,
     song_ids: &[i64],
@@ -43,10 +77,7 @@ pub async fn fetch_songs_with_versions(
         return Ok(vec![]);
     }
 
-    let placeholders = repeat("?")
-        .take(song_ids.len())
-        .collect::<Vec<_>>()
-        .join(", ");
+    let placeholders = vec!["?"; song_ids.len()].join(", ");
     let sql = format!(
         r#"
         SELECT
@@ -56,9 +87,11 @@ pub async fn fetch_songs_with_versions(
             songs.artist AS song_artist,
             songs.min_bpm AS song_min_bpm,
             songs.max_bpm AS song_max_bpm,
+            songs.bpm_indeterminate AS song_bpm_indeterminate,
             songs.unlock_info AS song_unlock_info,
             songs.version_id AS version_id,
             versions.name AS version_name,
+            versions.number AS version_number,
             versions.abbrev AS version_abbrev
         FROM songs
         INNER JOIN versions ON songs.version_id = versions.id
@@ -85,6 +118,7 @@ pub async fn fetch_song(pool: &SqlitePool, id: i64) -> SqlxResult<Option<Song>>
             songs.artist AS song_artist,
             songs.min_bpm AS song_min_bpm,
             songs.max_bpm AS song_max_bpm,
+            songs.bpm_indeterminate AS song_bpm_indeterminate,
             songs.unlock_info AS song_unlock_info
         FROM songs
         WHERE songs.id = ?;
@@ -97,15 +131,93 @@ pub async fn fetch_song(pool: &SqlitePool, id: i64) -> SqlxResult<Option<Song>>
     Ok(row)
 }
 
+pub async fn fetch_versions(pool: &SqlitePool) -> SqlxResult<Vec<Version>> {
+    let rows = sqlx::query_as(
+        r#"
+        SELECT
+            versions.id AS version_id,
+            versions.name AS version_name,
+            versions.number AS version_number,
+            versions.abbrev AS version_abbrev
+        FROM versions
+        ORDER BY versions.number, versions.id;
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn fetch_version(pool: &SqlitePool, id: i64) -> SqlxResult<Option<Version>> {
+    let row = sqlx::query_as(
+        r#"
+        SELECT
+            versions.id AS version_id,
+            versions.name AS version_name,
+            versions.number AS version_number,
+            versions.abbrev AS version_abbrev
+        FROM versions
+        WHERE versions.id = ?;
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+/// Fetches a page of songs debuted in the version, with the number of all of them.
+pub async fn fetch_songs_by_version(
+    pool: &SqlitePool,
+    version_id: i64,
+    sort: SongSort,
+    order: SortOrder,
+    limit: usize,
+    offset: usize,
+) -> SqlxResult<(Vec<Song>, i64)> {
+    let sql = format!(
+        r#"
+        SELECT
+            songs.id AS song_id,
+            songs.version_id AS version_id,
+            songs.genre AS song_genre,
+            songs.title AS song_title,
+            songs.artist AS song_artist,
+            songs.min_bpm AS song_min_bpm,
+            songs.max_bpm AS song_max_bpm,
+            songs.bpm_indeterminate AS song_bpm_indeterminate,
+            songs.unlock_info AS song_unlock_info,
+            (SELECT MAX(diffs.level) FROM diffs WHERE diffs.song_id = songs.id) AS song_max_level
+        FROM songs
+        WHERE songs.version_id = ?
+        ORDER BY {}
+        LIMIT ? OFFSET ?;
+        "#,
+        sort.order_by_str(order),
+    );
+    let rows: Vec<Song> = sqlx::query_as(&sql)
+        .bind(version_id)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(pool)
+        .await?;
+
+    let (total,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM songs WHERE songs.version_id = ?;")
+        .bind(version_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok((rows, total))
+}
+
 pub async fn fetch_diffs_by_song_ids(pool: &SqlitePool, song_ids: &[i64]) -> SqlxResult<Vec<Diff>> {
     if song_ids.is_empty() {
         return Ok(vec![]);
     }
 
-    let placeholders = repeat("?")
-        .take(song_ids.len())
-        .collect::<Vec<_>>()
-        .join(", ");
+    let placeholders = vec!["?"; song_ids.len()].join(", ");
     let sql = format!(
         r#"
         SELECT
@@ -113,9 +225,18 @@ pub async fn fetch_diffs_by_song_ids(pool: &SqlitePool, song_ids: &[i64]) -> Sql
             diffs.play_side AS diff_play_side,
             diffs.difficulty AS diff_difficulty,
             diffs.level AS diff_level,
+            diffs.notes AS diff_notes,
+            chart_bpms.min_bpm AS diff_min_bpm,
+            chart_bpms.max_bpm AS diff_max_bpm,
+            chart_bpms.indeterminate AS diff_bpm_indeterminate,
+            diffs.added_version_id AS diff_added_version_id,
             diffs.cn_type AS diff_note_type,
             diffs.bss_type AS diff_scratch_type
         FROM diffs
+        INNER JOIN chart_bpms
+            ON diffs.song_id = chart_bpms.song_id
+            AND diffs.play_side = chart_bpms.play_side
+            AND diffs.difficulty = chart_bpms.difficulty
         WHERE diffs.song_id IN ({placeholders});
         "#
     );
@@ -128,6 +249,67 @@ pub async fn fetch_diffs_by_song_ids(pool: &SqlitePool, song_ids: &[i64]) -> Sql
     Ok(rows)
 }
 
+pub async fn fetch_tiers_by_song_ids(pool: &SqlitePool, song_ids: &[i64]) -> SqlxResult<Vec<Tier>> {
+    if song_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let placeholders = vec!["?"; song_ids.len()].join(", ");
+    let sql = format!(
+        r#"
+        SELECT
+            tiers.song_id AS song_id,
+            tiers.play_side AS tier_play_side,
+            tiers.difficulty AS tier_difficulty,
+            tier_tables.abbrev AS tier_table_abbrev,
+            tier_tables.name AS tier_table_name,
+            tiers.tier AS tier_tier
+        FROM tiers
+        INNER JOIN tier_tables ON tiers.tier_table_id = tier_tables.id
+        WHERE tiers.song_id IN ({placeholders})
+        ORDER BY tier_tables.id;
+        "#
+    );
+    let rows: Vec<Tier> = song_ids
+        .iter()
+        .fold(sqlx::query_as(&sql), |q, id| q.bind(id))
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+pub async fn fetch_availabilities_by_song_ids(
+    pool: &SqlitePool,
+    song_ids: &[i64],
+) -> SqlxResult<Vec<Availability>> {
+    if song_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let placeholders = vec!["?"; song_ids.len()].join(", ");
+    let sql = format!(
+        r#"
+        SELECT
+            song_id,
+            platform AS availability_platform,
+            added_version_number AS availability_added_version_number,
+            deleted AS availability_deleted,
+            deleted_version_number AS availability_deleted_version_number
+        FROM availabilities
+        WHERE song_id IN ({placeholders})
+        ORDER BY song_id, platform, rowid;
+        "#
+    );
+    let rows: Vec<Availability> = song_ids
+        .iter()
+        .fold(sqlx::query_as(&sql), |q, id| q.bind(id))
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
 pub async fn fetch_diffs_by_ids(
     pool: &SqlitePool,
     diff_ids: &[(i64, PlaySide, Difficulty)],
@@ -136,10 +318,7 @@ pub async fn fetch_diffs_by_ids(
         return Ok(vec![]);
     }
 
-    let placeholders = repeat("(?, ?, ?)")
-        .take(diff_ids.len())
-        .collect::<Vec<_>>()
-        .join(", ");
+    let placeholders = vec!["(?, ?, ?)"; diff_ids.len()].join(", ");
     let sql = format!(
         r#"
         SELECT
@@ -147,9 +326,18 @@ pub async fn fetch_diffs_by_ids(
             diffs.play_side AS diff_play_side,
             diffs.difficulty AS diff_difficulty,
             diffs.level AS diff_level,
+            diffs.notes AS diff_notes,
+            chart_bpms.min_bpm AS diff_min_bpm,
+            chart_bpms.max_bpm AS diff_max_bpm,
+            chart_bpms.indeterminate AS diff_bpm_indeterminate,
+            diffs.added_version_id AS diff_added_version_id,
             diffs.cn_type AS diff_note_type,
             diffs.bss_type AS diff_scratch_type
         FROM diffs
+        INNER JOIN chart_bpms
+            ON diffs.song_id = chart_bpms.song_id
+            AND diffs.play_side = chart_bpms.play_side
+            AND diffs.difficulty = chart_bpms.difficulty
         WHERE (diffs.song_id, diffs.play_side, diffs.difficulty) IN ({placeholders});
         "#
     );
@@ -165,42 +353,116 @@ pub async fn fetch_diffs_by_ids(
     Ok(rows)
 }
 
+/// Fetches diffs matching all queries. User-specific queries refer to `context`.
 pub async fn query_filter_diffs(
     pool: &SqlitePool,
     queries: &[FilterQuery],
-) -> SqlxResult<Vec<(i64, PlaySide, Difficulty)>> {
+    context: FilterContext<'_>,
+) -> SqlxResult<Vec<DiffCandidate>> {
     if queries.is_empty() {
         return Ok(vec![]);
     }
 
-    let where_clauses: Vec<_> = queries.iter().map(|q| q.where_clause_str()).collect();
     let sql = format!(
         r#"
         SELECT
             diffs.song_id,
             diffs.play_side,
-            diffs.difficulty
-        FROM diffs
-        INNER JOIN songs ON diffs.song_id = songs.id
-        INNER JOIN versions ON songs.version_id = versions.id
+            diffs.difficulty,
+            versions.number AS version_number
+        {}
         WHERE {};
         "#,
-        &where_clauses.join(" AND "),
+        FILTER_FROM_CLAUSE,
+        filter_where_clause(queries),
     );
-    let rows: Vec<(i64, PlaySide, Difficulty)> = queries
-        .iter()
-        .fold(sqlx::query_as(&sql), |stmt, q| match q {
-            FilterQuery::VersionNumber(n) => stmt.bind(n),
-            FilterQuery::PlaySide(ps) => stmt.bind(ps),
-            FilterQuery::Difficulty(d) => stmt.bind(d),
-            FilterQuery::Level(l) => stmt.bind(l),
-            FilterQuery::Soflan(hs) => stmt.bind(hs),
-            FilterQuery::Note(nt) => stmt.bind(nt.to_string()),
-            FilterQuery::Scratch(st) => stmt.bind(st.to_string()),
-            FilterQuery::BpmRange(r) => stmt.bind(r.start()).bind(r.end()),
-        })
+    let rows: Vec<DiffCandidate> = bind_filter_queries(sqlx::query_as(&sql), queries, context)
         .fetch_all(pool)
         .await?;
 
     Ok(rows)
 }
+
+/// Counts diffs matching filters for each group. Empty filters match all diffs.
+pub async fn query_diff_stats(
+    pool: &SqlitePool,
+    queries: &[FilterQuery],
+    context: FilterContext<'_>,
+    group: StatsGroup,
+) -> SqlxResult<Vec<StatsCount>> {
+    let (key_expr, order_expr) = group.key_exprs_str();
+    let sql = format!(
+        r#"
+        SELECT
+            {key_expr} AS stats_key,
+            COUNT(*) AS stats_count
+        {}
+        WHERE {}
+        GROUP BY stats_key
+        ORDER BY MIN({order_expr});
+        "#,
+        FILTER_FROM_CLAUSE,
+        filter_where_clause(queries),
+    );
+
+    // bucket widths appear in the SELECT, the filters, then the ORDER BY
+    let stmt = sqlx::query_as(&sql);
+    let stmt = match group.bucket_width() {
+        Some(width) => stmt.bind(width).bind(width),
+        None => stmt,
+    };
+    let stmt = bind_filter_queries(stmt, queries, context);
+    let stmt = match group.bucket_width() {
+        Some(width) => stmt.bind(width).bind(width),
+        None => stmt,
+    };
+    let rows: Vec<StatsCount> = stmt.fetch_all(pool).await?;
+
+    Ok(rows)
+}
+
+/// Tables referred by `FilterQuery::where_clause_str`.
+const FILTER_FROM_CLAUSE: &str = r#"
+        FROM diffs
+        INNER JOIN songs ON diffs.song_id = songs.id
+        INNER JOIN versions ON songs.version_id = versions.id
+        LEFT JOIN versions AS chart_versions ON diffs.added_version_id = chart_versions.id
+        INNER JOIN chart_bpms
+            ON diffs.song_id = chart_bpms.song_id
+            AND diffs.play_side = chart_bpms.play_side
+            AND diffs.difficulty = chart_bpms.difficulty
+"#;
+
+fn filter_where_clause(queries: &[FilterQuery]) -> String {
+    if queries.is_empty() {
+        return "TRUE".into();
+    }
+
+    let where_clauses: Vec<_> = queries.iter().map(|q| q.where_clause_str()).collect();
+    where_clauses.join(" AND ")
+}
+
+fn bind_filter_queries<'q, O>(
+    stmt: QueryAs<'q, Sqlite, O, SqliteArguments<'q>>,
+    queries: &'q [FilterQuery],
+    context: FilterContext<'q>,
+) -> QueryAs<'q, Sqlite, O, SqliteArguments<'q>> {
+    queries.iter().fold(stmt, |stmt, q| match q {
+        FilterQuery::VersionNumber(_, n) => stmt.bind(n),
+        FilterQuery::PlaySide(ps) => stmt.bind(ps),
+        FilterQuery::Difficulty(d) => stmt.bind(d),
+        FilterQuery::Level(l) => stmt.bind(l),
+        FilterQuery::Soflan(hs) => stmt.bind(hs),
+        FilterQuery::BpmIndeterminate => stmt,
+        FilterQuery::Note(nt) => stmt.bind(nt.to_string()),
+        FilterQuery::Scratch(st) => stmt.bind(st.to_string()),
+        FilterQuery::BpmRange(r) => stmt.bind(r.start()).bind(r.end()),
+        FilterQuery::NotesRange(r) => stmt.bind(r.start()).bind(r.end()),
+        FilterQuery::Tier(table, tier) => stmt.bind(table).bind(tier),
+        FilterQuery::Lamp(_, lamp) => stmt.bind(context.user_name).bind(lamp),
+        FilterQuery::List(scope, name) => {
+            stmt.bind(scope).bind(context.list_owner(*scope)).bind(name)
+        }
+        FilterQuery::Available(platform) => stmt.bind(platform),
+    })
+}
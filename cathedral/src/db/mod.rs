@@ -1,2 +1,3 @@
 pub mod function;
 pub mod schema;
+pub mod userdata;
@@ -5,21 +5,24 @@ use std::{
     str::FromStr,
 };
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Type as SqlxType};
 use thiserror::Error as ThisError;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, ToSchema)]
 pub struct Version {
     #[sqlx(rename = "version_id")]
     pub id: i64,
     #[sqlx(rename = "version_name")]
     pub name: String,
+    #[sqlx(rename = "version_number")]
+    pub number: i64,
     #[sqlx(rename = "version_abbrev")]
     pub abbrev: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, ToSchema)]
 pub struct Song {
     pub version_id: i64,
 
@@ -34,12 +37,14 @@ pub struct Song {
     #[sqlx(rename = "song_min_bpm")]
     pub min_bpm: Option<i64>,
     #[sqlx(rename = "song_max_bpm")]
-    pub max_bpm: i64,
+    pub max_bpm: Option<i64>,
+    #[sqlx(rename = "song_bpm_indeterminate")]
+    pub bpm_indeterminate: bool,
     #[sqlx(rename = "song_unlock_info")]
     pub unlock_info: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, ToSchema)]
 pub struct Diff {
     pub song_id: i64,
 
@@ -49,13 +54,172 @@ pub struct Diff {
     pub difficulty: Difficulty,
     #[sqlx(rename = "diff_level")]
     pub level: i64,
+    #[sqlx(rename = "diff_notes")]
+    pub notes: Option<i64>,
+    /// Effective tempo of this chart, `None` if indeterminate.
+    #[sqlx(rename = "diff_min_bpm")]
+    pub min_bpm: Option<i64>,
+    #[sqlx(rename = "diff_max_bpm")]
+    pub max_bpm: Option<i64>,
+    #[sqlx(rename = "diff_bpm_indeterminate")]
+    pub bpm_indeterminate: bool,
+    /// Version in which this chart was added, `None` if it debuted with the song.
+    #[sqlx(rename = "diff_added_version_id")]
+    pub added_version_id: Option<i64>,
     #[sqlx(rename = "diff_note_type")]
     pub note_type: Option<NoteType>,
     #[sqlx(rename = "diff_scratch_type")]
     pub scratch_type: Option<ScratchType>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, SqlxType, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, ToSchema)]
+pub struct Tier {
+    pub song_id: i64,
+
+    #[sqlx(rename = "tier_play_side")]
+    pub play_side: PlaySide,
+    #[sqlx(rename = "tier_difficulty")]
+    pub difficulty: Difficulty,
+    #[sqlx(rename = "tier_table_abbrev")]
+    pub table_abbrev: String,
+    #[sqlx(rename = "tier_table_name")]
+    pub table_name: String,
+    #[sqlx(rename = "tier_tier")]
+    pub tier: String,
+}
+
+/// Period in which the song is playable on the platform.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, ToSchema)]
+pub struct Availability {
+    pub song_id: i64,
+
+    #[sqlx(rename = "availability_platform")]
+    pub platform: Platform,
+    #[sqlx(rename = "availability_added_version_number")]
+    pub added_version_number: Option<i64>,
+    #[sqlx(rename = "availability_deleted")]
+    pub deleted: bool,
+    #[sqlx(rename = "availability_deleted_version_number")]
+    pub deleted_version_number: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, ToSchema)]
+pub struct Score {
+    pub user_name: String,
+    pub song_id: i64,
+
+    #[sqlx(rename = "score_play_side")]
+    pub play_side: PlaySide,
+    #[sqlx(rename = "score_difficulty")]
+    pub difficulty: Difficulty,
+    #[sqlx(rename = "score_lamp")]
+    pub lamp: Lamp,
+    #[sqlx(rename = "score_ex_score")]
+    pub ex_score: Option<i64>,
+    #[sqlx(rename = "score_miss_count")]
+    pub miss_count: Option<i64>,
+    #[sqlx(rename = "score_updated_at")]
+    pub updated_at: i64,
+}
+
+/// Score fields to update. `None` keeps the current value.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, ToSchema)]
+pub struct ScoreUpdate {
+    pub song_id: i64,
+    pub play_side: PlaySide,
+    pub difficulty: Difficulty,
+    pub lamp: Option<Lamp>,
+    pub ex_score: Option<i64>,
+    pub miss_count: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SqlxType, Serialize, Deserialize, ToSchema)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ListScope {
+    User,
+    Channel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, ToSchema)]
+pub struct DiffList {
+    #[sqlx(rename = "list_id")]
+    pub id: i64,
+    #[sqlx(rename = "list_scope")]
+    pub scope: ListScope,
+    #[sqlx(rename = "list_owner")]
+    pub owner: String,
+    #[sqlx(rename = "list_name")]
+    pub name: String,
+}
+
+/// Requester of filter queries, which user-specific queries refer to.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilterContext<'a> {
+    pub user_name: &'a str,
+    pub channel_id: &'a str,
+}
+
+impl<'a> FilterContext<'a> {
+    pub fn list_owner(&self, scope: ListScope) -> &'a str {
+        match scope {
+            ListScope::User => self.user_name,
+            ListScope::Channel => self.channel_id,
+        }
+    }
+}
+
+/// Sort key of song listings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SongSort {
+    #[default]
+    Title,
+
+    /// Highest level among the song's diffs.
+    Level,
+
+    /// Maximum tempo of the song.
+    Bpm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SongSort {
+    pub fn order_by_str(&self, order: SortOrder) -> &'static str {
+        match (self, order) {
+            (SongSort::Title, SortOrder::Asc) => "songs.title ASC, songs.id ASC",
+            (SongSort::Title, SortOrder::Desc) => "songs.title DESC, songs.id DESC",
+            (SongSort::Level, SortOrder::Asc) => "song_max_level ASC, songs.id ASC",
+            (SongSort::Level, SortOrder::Desc) => "song_max_level DESC, songs.id DESC",
+            (SongSort::Bpm, SortOrder::Asc) => "songs.max_bpm ASC, songs.id ASC",
+            (SongSort::Bpm, SortOrder::Desc) => "songs.max_bpm DESC, songs.id DESC",
+        }
+    }
+}
+
+/// Candidate diff returned by filter queries, with metadata for weighted selection.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct DiffCandidate {
+    pub song_id: i64,
+    pub play_side: PlaySide,
+    pub difficulty: Difficulty,
+    pub version_number: i64,
+}
+
+impl DiffCandidate {
+    pub fn id(&self) -> (i64, PlaySide, Difficulty) {
+        (self.song_id, self.play_side, self.difficulty)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SqlxType, Serialize, Deserialize, ToSchema)]
 pub enum PlaySide {
     #[sqlx(rename = "SP")]
     #[serde(rename = "SP")]
@@ -74,7 +238,20 @@ impl Display for PlaySide {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SqlxType, Serialize)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    SqlxType,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
 #[sqlx(rename_all = "SCREAMING_SNAKE_CASE")]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Difficulty {
@@ -85,18 +262,6 @@ pub enum Difficulty {
     Leggendaria,
 }
 
-impl Difficulty {
-    pub fn to_emoji_str(self) -> &'static str {
-        match self {
-            Difficulty::Beginner => ":diff-b:",
-            Difficulty::Normal => ":diff-n:",
-            Difficulty::Hyper => ":diff-h:",
-            Difficulty::Another => ":diff-a:",
-            Difficulty::Leggendaria => ":diff-l:",
-        }
-    }
-}
-
 impl Display for Difficulty {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
@@ -109,7 +274,16 @@ impl Display for Difficulty {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, SqlxType, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SqlxType, Serialize, ToSchema)]
+#[sqlx(rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Platform {
+    Arcade,
+    Infinitas,
+    Consumer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SqlxType, Serialize, ToSchema)]
 pub enum NoteType {
     #[sqlx(rename = "CN")]
     #[serde(rename = "CN")]
@@ -128,7 +302,7 @@ impl Display for NoteType {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, SqlxType, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SqlxType, Serialize, ToSchema)]
 pub enum ScratchType {
     #[sqlx(rename = "BSS")]
     #[serde(rename = "BSS")]
@@ -151,10 +325,71 @@ impl Display for ScratchType {
     }
 }
 
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    SqlxType,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[repr(i64)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Lamp {
+    NoPlay = 0,
+    Failed = 1,
+    AssistClear = 2,
+    EasyClear = 3,
+    Clear = 4,
+    HardClear = 5,
+    ExHardClear = 6,
+    FullCombo = 7,
+}
+
+impl Display for Lamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Lamp::NoPlay => f.write_str("NO PLAY"),
+            Lamp::Failed => f.write_str("FAILED"),
+            Lamp::AssistClear => f.write_str("ASSIST CLEAR"),
+            Lamp::EasyClear => f.write_str("EASY CLEAR"),
+            Lamp::Clear => f.write_str("CLEAR"),
+            Lamp::HardClear => f.write_str("HARD CLEAR"),
+            Lamp::ExHardClear => f.write_str("EX HARD CLEAR"),
+            Lamp::FullCombo => f.write_str("FULLCOMBO CLEAR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+}
+
+/// Which version `FilterQuery::VersionNumber` refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionTarget {
+    /// Version in which the song debuted.
+    Song,
+
+    /// Version in which the chart was added, falling back to the song's one.
+    Chart,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FilterQuery {
-    /// `versions.number = ?`
-    VersionNumber(i64),
+    /// `versions.number = ?` or `COALESCE(chart_versions.number, versions.number) = ?`
+    VersionNumber(VersionTarget, i64),
 
     /// `diffs.play_side = ?`
     PlaySide(PlaySide),
@@ -165,30 +400,122 @@ pub enum FilterQuery {
     /// `diffs.level = ?`
     Level(i64),
 
-    /// `(songs.min_bpm IS NOT NULL) = ?`
+    /// `(chart_bpms.min_bpm IS NOT NULL) = ?`, excluding indeterminate
     Soflan(bool),
 
+    /// `chart_bpms.indeterminate`
+    BpmIndeterminate,
+
     /// `songs.cn_type = ?`
     Note(NoteType),
 
     /// `songs.cn_type = ?`
     Scratch(ScratchType),
 
-    /// `songs.max_bpm BETWEEN ? AND ?`
+    /// `chart_bpms.max_bpm BETWEEN ? AND ?`
     BpmRange(RangeInclusive<i64>),
+
+    /// `diffs.notes BETWEEN ? AND ?`
+    NotesRange(RangeInclusive<i64>),
+
+    /// `tier_tables.abbrev = ? AND tiers.tier = ?` for the diff
+    Tier(String, String),
+
+    /// `userdata.scores.lamp < ?` etc. for the requesting user, no play if absent
+    Lamp(Comparison, Lamp),
+
+    /// diff is in `userdata.lists` of the requesting user or channel
+    List(ListScope, String),
+
+    /// `availabilities.platform = ?` and not deleted for the song
+    Available(Platform),
+}
+
+/// Lamp of the diff for the user bound first.
+macro_rules! lamp_subquery {
+    () => {
+        r#"
+        COALESCE((
+            SELECT userdata.scores.lamp
+            FROM userdata.scores
+            WHERE
+                userdata.scores.user_name = ?
+                AND userdata.scores.song_id = diffs.song_id
+                AND userdata.scores.play_side = diffs.play_side
+                AND userdata.scores.difficulty = diffs.difficulty
+        ), 0)
+        "#
+    };
 }
 
 impl FilterQuery {
     pub fn where_clause_str(&self) -> &'static str {
         match self {
-            FilterQuery::VersionNumber(_) => "versions.number = ?",
+            FilterQuery::VersionNumber(VersionTarget::Song, _) => "versions.number = ?",
+            FilterQuery::VersionNumber(VersionTarget::Chart, _) => {
+                "COALESCE(chart_versions.number, versions.number) = ?"
+            }
             FilterQuery::PlaySide(_) => "diffs.play_side = ?",
             FilterQuery::Difficulty(_) => "diffs.difficulty = ?",
             FilterQuery::Level(_) => "diffs.level = ?",
-            FilterQuery::Soflan(_) => "(songs.min_bpm IS NOT NULL) = ?",
+            FilterQuery::Soflan(_) => {
+                "(chart_bpms.min_bpm IS NOT NULL) = ? AND chart_bpms.max_bpm IS NOT NULL"
+            }
+            FilterQuery::BpmIndeterminate => "chart_bpms.indeterminate",
             FilterQuery::Note(_) => "diffs.cn_type = ?",
             FilterQuery::Scratch(_) => "diffs.bss_type = ?",
-            FilterQuery::BpmRange(_) => "songs.max_bpm BETWEEN ? and ?",
+            FilterQuery::BpmRange(_) => "chart_bpms.max_bpm BETWEEN ? and ?",
+            FilterQuery::NotesRange(_) => "diffs.notes BETWEEN ? and ?",
+            FilterQuery::Tier(_, _) => {
+                r#"
+                EXISTS (
+                    SELECT 1
+                    FROM tiers
+                    INNER JOIN tier_tables ON tiers.tier_table_id = tier_tables.id
+                    WHERE
+                        tiers.song_id = diffs.song_id
+                        AND tiers.play_side = diffs.play_side
+                        AND tiers.difficulty = diffs.difficulty
+                        AND tier_tables.abbrev = ?
+                        AND tiers.tier = ? COLLATE NOCASE
+                )
+                "#
+            }
+            FilterQuery::Lamp(comparison, _) => match comparison {
+                Comparison::Less => concat!(lamp_subquery!(), " < ?"),
+                Comparison::LessEqual => concat!(lamp_subquery!(), " <= ?"),
+                Comparison::Equal => concat!(lamp_subquery!(), " = ?"),
+                Comparison::GreaterEqual => concat!(lamp_subquery!(), " >= ?"),
+                Comparison::Greater => concat!(lamp_subquery!(), " > ?"),
+            },
+            FilterQuery::List(_, _) => {
+                r#"
+                EXISTS (
+                    SELECT 1
+                    FROM userdata.list_entries
+                    INNER JOIN userdata.lists ON userdata.list_entries.list_id = userdata.lists.id
+                    WHERE
+                        userdata.lists.scope = ?
+                        AND userdata.lists.owner = ?
+                        AND userdata.lists.name = ?
+                        AND userdata.list_entries.song_id = diffs.song_id
+                        AND userdata.list_entries.play_side = diffs.play_side
+                        AND userdata.list_entries.difficulty = diffs.difficulty
+                )
+                "#
+            }
+            FilterQuery::Available(_) => {
+                r#"
+                EXISTS (
+                    SELECT 1
+                    FROM availabilities
+                    WHERE
+                        availabilities.song_id = diffs.song_id
+                        AND availabilities.platform = ?
+                        AND NOT availabilities.deleted
+                )
+                "#
+            }
         }
     }
 }
@@ -221,7 +548,11 @@ impl FromStr for FilterQuery {
         match qtype {
             "v" | "version" => {
                 let number: i64 = value.parse()?;
-                Ok(FilterQuery::VersionNumber(number))
+                Ok(FilterQuery::VersionNumber(VersionTarget::Song, number))
+            }
+            "cv" | "chartversion" => {
+                let number: i64 = value.parse()?;
+                Ok(FilterQuery::VersionNumber(VersionTarget::Chart, number))
             }
             "p" | "play" => match value {
                 "s" | "sp" => Ok(FilterQuery::PlaySide(PlaySide::Single)),
@@ -243,6 +574,7 @@ impl FromStr for FilterQuery {
             "f" | "soflan" => match value {
                 "y" | "yes" | "t" | "true" => Ok(FilterQuery::Soflan(true)),
                 "n" | "no" | "f" | "false" => Ok(FilterQuery::Soflan(false)),
+                "u" | "unknown" => Ok(FilterQuery::BpmIndeterminate),
                 _ => Err(FilterQueryError::InvalidValue(value.into())),
             },
             "n" | "note" => match value {
@@ -256,24 +588,182 @@ impl FromStr for FilterQuery {
                 "m" | "mss" => Ok(FilterQuery::Scratch(ScratchType::Multi)),
                 _ => Err(FilterQueryError::InvalidValue(value.into())),
             },
-            "b" | "bpm" => {
-                let mut bpms = value.split('-');
-                let first = bpms.next();
-                let second = bpms.next();
-                let range = match (first, second) {
-                    (Some(fixed), None) => {
-                        let v = fixed.parse()?;
-                        v..=v
-                    }
-                    (Some(""), Some("")) => i64::MIN..=i64::MAX,
-                    (Some(""), Some(upper)) => i64::MIN..=(upper.parse()?),
-                    (Some(lower), Some("")) => (lower.parse()?)..=i64::MAX,
-                    (Some(lower), Some(upper)) => (lower.parse()?)..=(upper.parse()?),
-                    (None, _) => unreachable!("at least one element"),
+            "b" | "bpm" => Ok(FilterQuery::BpmRange(parse_range(value)?)),
+            "nc" | "notes" => Ok(FilterQuery::NotesRange(parse_range(value)?)),
+            "t" | "tier" => {
+                // tier:<table>/<tier>, like tier:hc/S+
+                let Some((table, tier)) = value.split_once('/') else {
+                    return Err(FilterQueryError::InvalidValue(value.into()));
+                };
+                Ok(FilterQuery::Tier(table.into(), tier.into()))
+            }
+            "lamp" => {
+                let (comparison, lamp_str) = parse_comparison(value);
+                let lamp = match lamp_str {
+                    "np" | "noplay" => Lamp::NoPlay,
+                    "f" | "failed" => Lamp::Failed,
+                    "a" | "ac" | "assist" => Lamp::AssistClear,
+                    "e" | "ec" | "easy" => Lamp::EasyClear,
+                    "c" | "nc" | "clear" => Lamp::Clear,
+                    "h" | "hc" | "hard" => Lamp::HardClear,
+                    "ex" | "exh" | "exhard" => Lamp::ExHardClear,
+                    "fc" | "fullcombo" => Lamp::FullCombo,
+                    _ => return Err(FilterQueryError::InvalidValue(value.into())),
                 };
-                Ok(FilterQuery::BpmRange(range))
+                Ok(FilterQuery::Lamp(comparison, lamp))
+            }
+            "list" => {
+                // list:<name> for the user, list:#<name> for the channel
+                let (scope, name) = parse_list_name(value);
+                if name.is_empty() {
+                    return Err(FilterQueryError::InvalidValue(value.into()));
+                }
+                Ok(FilterQuery::List(scope, name.into()))
             }
+            "avail" => match value {
+                "ac" | "arcade" => Ok(FilterQuery::Available(Platform::Arcade)),
+                "inf" | "infinitas" => Ok(FilterQuery::Available(Platform::Infinitas)),
+                "cs" | "consumer" => Ok(FilterQuery::Available(Platform::Consumer)),
+                _ => Err(FilterQueryError::InvalidValue(value.into())),
+            },
             _ => Err(FilterQueryError::UnknownQuery(qtype.into())),
         }
     }
 }
+
+/// Grouping key of catalog statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsGroup {
+    /// `versions.number`
+    Version,
+
+    /// `diffs.level`
+    Level,
+
+    /// `diffs.difficulty`
+    Difficulty,
+
+    /// `diffs.play_side`
+    PlaySide,
+
+    /// `diffs.cn_type`
+    Note,
+
+    /// `diffs.bss_type`
+    Scratch,
+
+    /// `chart_bpms.max_bpm` in buckets of the width
+    Bpm(i64),
+
+    /// `diffs.notes` in buckets of the width
+    Notes(i64),
+}
+
+impl StatsGroup {
+    /// Returns the label and ordering expressions, in this order.
+    pub fn key_exprs_str(&self) -> (&'static str, &'static str) {
+        match self {
+            StatsGroup::Version => ("versions.abbrev", "versions.number"),
+            StatsGroup::Level => ("CAST(diffs.level AS TEXT)", "diffs.level"),
+            StatsGroup::Difficulty => (
+                "diffs.difficulty",
+                "(SELECT rowid FROM difficulties WHERE difficulties.name = diffs.difficulty)",
+            ),
+            StatsGroup::PlaySide => ("diffs.play_side", "diffs.play_side"),
+            StatsGroup::Note => ("diffs.cn_type", "diffs.cn_type"),
+            StatsGroup::Scratch => ("diffs.bss_type", "diffs.bss_type"),
+            StatsGroup::Bpm(_) => (
+                "CAST(chart_bpms.max_bpm / ? * ? AS TEXT)",
+                "chart_bpms.max_bpm / ? * ?",
+            ),
+            StatsGroup::Notes(_) => ("CAST(diffs.notes / ? * ? AS TEXT)", "diffs.notes / ? * ?"),
+        }
+    }
+
+    /// Bucket width bound to each of the expressions, if histogram.
+    pub fn bucket_width(&self) -> Option<i64> {
+        match self {
+            StatsGroup::Bpm(width) | StatsGroup::Notes(width) => Some(*width),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for StatsGroup {
+    type Err = FilterQueryError;
+
+    fn from_str(s: &str) -> Result<StatsGroup, FilterQueryError> {
+        let (gtype, width) = match s.split_once(':') {
+            Some((gtype, width)) => (gtype, Some(width.parse::<i64>()?)),
+            None => (s, None),
+        };
+        if width.map(|w| w <= 0).unwrap_or(false) {
+            return Err(FilterQueryError::InvalidValue(s.into()));
+        }
+
+        match gtype {
+            "v" | "version" => Ok(StatsGroup::Version),
+            "l" | "level" => Ok(StatsGroup::Level),
+            "d" | "diff" | "difficulty" => Ok(StatsGroup::Difficulty),
+            "p" | "play" | "playside" => Ok(StatsGroup::PlaySide),
+            "n" | "note" => Ok(StatsGroup::Note),
+            "s" | "scratch" => Ok(StatsGroup::Scratch),
+            "b" | "bpm" => Ok(StatsGroup::Bpm(width.unwrap_or(10))),
+            "nc" | "notes" => Ok(StatsGroup::Notes(width.unwrap_or(100))),
+            _ => Err(FilterQueryError::UnknownQuery(gtype.into())),
+        }
+    }
+}
+
+/// Number of diffs in a group. `key` is `None` for diffs without the value.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, ToSchema)]
+pub struct StatsCount {
+    #[sqlx(rename = "stats_key")]
+    pub key: Option<String>,
+    #[sqlx(rename = "stats_count")]
+    pub count: i64,
+}
+
+/// Splits list scope from its name. `#` prefix means the channel list.
+pub fn parse_list_name(value: &str) -> (ListScope, &str) {
+    match value.strip_prefix('#') {
+        Some(name) => (ListScope::Channel, name),
+        None => (ListScope::User, value),
+    }
+}
+
+/// Splits leading comparison operator. Defaults to `Equal`.
+fn parse_comparison(value: &str) -> (Comparison, &str) {
+    let operators = [
+        ("<=", Comparison::LessEqual),
+        (">=", Comparison::GreaterEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+        ("=", Comparison::Equal),
+    ];
+    for (operator, comparison) in operators {
+        if let Some(rest) = value.strip_prefix(operator) {
+            return (comparison, rest);
+        }
+    }
+    (Comparison::Equal, value)
+}
+
+/// Parses `100`, `100-200`, `100-` or `-200` as an inclusive range.
+fn parse_range(value: &str) -> Result<RangeInclusive<i64>, FilterQueryError> {
+    let mut parts = value.split('-');
+    let first = parts.next();
+    let second = parts.next();
+    let range = match (first, second) {
+        (Some(fixed), None) => {
+            let v = fixed.parse()?;
+            v..=v
+        }
+        (Some(""), Some("")) => i64::MIN..=i64::MAX,
+        (Some(""), Some(upper)) => i64::MIN..=(upper.parse()?),
+        (Some(lower), Some("")) => (lower.parse()?)..=i64::MAX,
+        (Some(lower), Some(upper)) => (lower.parse()?)..=(upper.parse()?),
+        (None, _) => unreachable!("at least one element"),
+    };
+    Ok(range)
+}
@@ -0,0 +1,401 @@
+use crate::db::schema::{DiffList, Difficulty, ListScope, PlaySide, Score, ScoreUpdate};
+
+use std::path::Path;
+
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Result as SqlxResult, SqlitePool,
+};
+
+/// Shared-cache in-memory database, which the catalog connections can attach too.
+const MEMORY_USERDATA_URI: &str = "file:cathedral-userdata?mode=memory&cache=shared";
+
+/// Schema scripts of user data store in order. `PRAGMA user_version` counts the applied ones.
+const USERDATA_MIGRATIONS: &[&str] = &[
+    include_str!("../../../sql/userdata/01-draw_history.sql"),
+    include_str!("../../../sql/userdata/02-scores.sql"),
+    include_str!("../../../sql/userdata/03-lists.sql"),
+];
+
+/// Opens writable user data store. Creates the file if not exists.
+pub async fn open_userdata_file(path: &Path) -> SqlxResult<SqlitePool> {
+    let conn = SqlitePool::connect(&format!(
+        "sqlite://{}?mode=rwc",
+        path.to_str().expect("invalid filename")
+    ))
+    .await?;
+    Ok(conn)
+}
+
+/// Opens volatile user data store. It lives only while the process is running.
+pub async fn open_userdata_memory() -> SqlxResult<SqlitePool> {
+    // in-memory database is dropped with its last connection, so keep one
+    let conn = SqlitePoolOptions::new()
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(SqliteConnectOptions::new().filename(MEMORY_USERDATA_URI))
+        .await?;
+    Ok(conn)
+}
+
+/// SQLite URI to attach the user data store to catalog connections as read-only.
+pub fn userdata_attach_uri(path: Option<&Path>) -> String {
+    let Some(path) = path else {
+        return MEMORY_USERDATA_URI.into();
+    };
+
+    let escaped = path
+        .to_str()
+        .expect("invalid filename")
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    format!("file:{escaped}?mode=ro")
+}
+
+/// Applies schema scripts which are not applied yet.
+pub async fn migrate_userdata(pool: &SqlitePool) -> SqlxResult<()> {
+    let mut tx = pool.begin().await?;
+    let (applied,): (i64,) = sqlx::query_as("PRAGMA user_version;")
+        .fetch_one(&mut *tx)
+        .await?;
+    for (index, script) in USERDATA_MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(applied as usize)
+    {
+        sqlx::query(script).execute(&mut *tx).await?;
+        sqlx::query(&format!("PRAGMA user_version = {};", index + 1))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn insert_draws(
+    pool: &SqlitePool,
+    channel_id: &str,
+    user_id: &str,
+    diff_ids: &[(i64, PlaySide, Difficulty)],
+) -> SqlxResult<()> {
+    let mut tx = pool.begin().await?;
+    for (song_id, play_side, difficulty) in diff_ids {
+        sqlx::query(
+            r#"
+            INSERT INTO "draw_history" ("channel_id", "user_id", "song_id", "play_side", "difficulty", "drawn_at")
+            VALUES (?, ?, ?, ?, ?, strftime('%s', 'now'));
+            "#,
+        )
+        .bind(channel_id)
+        .bind(user_id)
+        .bind(song_id)
+        .bind(play_side)
+        .bind(difficulty)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Fetches diffs drawn recently, latest first. `None` filters match any channel or user.
+pub async fn fetch_recent_draws(
+    pool: &SqlitePool,
+    channel_id: Option<&str>,
+    user_id: Option<&str>,
+    window: usize,
+) -> SqlxResult<Vec<(i64, PlaySide, Difficulty)>> {
+    let rows = sqlx::query_as(
+        r#"
+        SELECT
+            draw_history.song_id,
+            draw_history.play_side,
+            draw_history.difficulty
+        FROM draw_history
+        WHERE
+            (? IS NULL OR draw_history.channel_id = ?) AND
+            (? IS NULL OR draw_history.user_id = ?)
+        ORDER BY draw_history.id DESC
+        LIMIT ?;
+        "#,
+    )
+    .bind(channel_id)
+    .bind(channel_id)
+    .bind(user_id)
+    .bind(user_id)
+    .bind(window as i64)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Deletes draws which no longer fit in the window. Filters are the same as `fetch_recent_draws`.
+pub async fn prune_draws(
+    pool: &SqlitePool,
+    channel_id: Option<&str>,
+    user_id: Option<&str>,
+    window: usize,
+) -> SqlxResult<()> {
+    sqlx::query(
+        r#"
+        DELETE FROM draw_history
+        WHERE
+            (? IS NULL OR draw_history.channel_id = ?) AND
+            (? IS NULL OR draw_history.user_id = ?) AND
+            draw_history.id NOT IN (
+                SELECT recent.id
+                FROM draw_history AS recent
+                WHERE
+                    (? IS NULL OR recent.channel_id = ?) AND
+                    (? IS NULL OR recent.user_id = ?)
+                ORDER BY recent.id DESC
+                LIMIT ?
+            );
+        "#,
+    )
+    .bind(channel_id)
+    .bind(channel_id)
+    .bind(user_id)
+    .bind(user_id)
+    .bind(channel_id)
+    .bind(channel_id)
+    .bind(user_id)
+    .bind(user_id)
+    .bind(window as i64)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fetch_scores(
+    pool: &SqlitePool,
+    user_name: &str,
+    song_id: Option<i64>,
+) -> SqlxResult<Vec<Score>> {
+    let rows = sqlx::query_as(
+        r#"
+        SELECT
+            scores.user_name AS user_name,
+            scores.song_id AS song_id,
+            scores.play_side AS score_play_side,
+            scores.difficulty AS score_difficulty,
+            scores.lamp AS score_lamp,
+            scores.ex_score AS score_ex_score,
+            scores.miss_count AS score_miss_count,
+            scores.updated_at AS score_updated_at
+        FROM scores
+        WHERE scores.user_name = ? AND (? IS NULL OR scores.song_id = ?)
+        ORDER BY scores.song_id, scores.play_side, scores.difficulty;
+        "#,
+    )
+    .bind(user_name)
+    .bind(song_id)
+    .bind(song_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Inserts or updates score. Absent fields keep existing values.
+pub async fn upsert_score(
+    pool: &SqlitePool,
+    user_name: &str,
+    update: &ScoreUpdate,
+) -> SqlxResult<Score> {
+    let row = sqlx::query_as(
+        r#"
+        INSERT INTO "scores" ("user_name", "song_id", "play_side", "difficulty", "lamp", "ex_score", "miss_count", "updated_at")
+        VALUES (?, ?, ?, ?, COALESCE(?, 0), ?, ?, strftime('%s', 'now'))
+        ON CONFLICT DO UPDATE SET
+            "lamp" = COALESCE(?, "lamp"),
+            "ex_score" = COALESCE(excluded."ex_score", "ex_score"),
+            "miss_count" = COALESCE(excluded."miss_count", "miss_count"),
+            "updated_at" = excluded."updated_at"
+        RETURNING
+            "user_name",
+            "song_id",
+            "play_side" AS "score_play_side",
+            "difficulty" AS "score_difficulty",
+            "lamp" AS "score_lamp",
+            "ex_score" AS "score_ex_score",
+            "miss_count" AS "score_miss_count",
+            "updated_at" AS "score_updated_at";
+        "#,
+    )
+    .bind(user_name)
+    .bind(update.song_id)
+    .bind(update.play_side)
+    .bind(update.difficulty)
+    .bind(update.lamp)
+    .bind(update.ex_score)
+    .bind(update.miss_count)
+    .bind(update.lamp)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn fetch_lists(
+    pool: &SqlitePool,
+    scope: ListScope,
+    owner: &str,
+) -> SqlxResult<Vec<DiffList>> {
+    let rows = sqlx::query_as(
+        r#"
+        SELECT
+            lists.id AS list_id,
+            lists.scope AS list_scope,
+            lists.owner AS list_owner,
+            lists.name AS list_name
+        FROM lists
+        WHERE lists.scope = ? AND lists.owner = ?
+        ORDER BY lists.name;
+        "#,
+    )
+    .bind(scope)
+    .bind(owner)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn fetch_list(
+    pool: &SqlitePool,
+    scope: ListScope,
+    owner: &str,
+    name: &str,
+) -> SqlxResult<Option<DiffList>> {
+    let row = sqlx::query_as(
+        r#"
+        SELECT
+            lists.id AS list_id,
+            lists.scope AS list_scope,
+            lists.owner AS list_owner,
+            lists.name AS list_name
+        FROM lists
+        WHERE lists.scope = ? AND lists.owner = ? AND lists.name = ?;
+        "#,
+    )
+    .bind(scope)
+    .bind(owner)
+    .bind(name)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+/// Creates list, or returns existing one with the same name.
+pub async fn create_list(
+    pool: &SqlitePool,
+    scope: ListScope,
+    owner: &str,
+    name: &str,
+) -> SqlxResult<DiffList> {
+    let row = sqlx::query_as(
+        r#"
+        INSERT INTO "lists" ("scope", "owner", "name")
+        VALUES (?, ?, ?)
+        ON CONFLICT DO UPDATE SET "name" = excluded."name"
+        RETURNING
+            "id" AS "list_id",
+            "scope" AS "list_scope",
+            "owner" AS "list_owner",
+            "name" AS "list_name";
+        "#,
+    )
+    .bind(scope)
+    .bind(owner)
+    .bind(name)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn delete_list(pool: &SqlitePool, list_id: i64) -> SqlxResult<()> {
+    sqlx::query(r#"DELETE FROM "lists" WHERE "id" = ?;"#)
+        .bind(list_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn fetch_list_entries(
+    pool: &SqlitePool,
+    list_id: i64,
+) -> SqlxResult<Vec<(i64, PlaySide, Difficulty)>> {
+    let rows = sqlx::query_as(
+        r#"
+        SELECT
+            list_entries.song_id,
+            list_entries.play_side,
+            list_entries.difficulty
+        FROM list_entries
+        WHERE list_entries.list_id = ?
+        ORDER BY list_entries.added_at, list_entries.song_id;
+        "#,
+    )
+    .bind(list_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Adds diffs to the list. Diffs already in the list are ignored.
+pub async fn insert_list_entries(
+    pool: &SqlitePool,
+    list_id: i64,
+    diff_ids: &[(i64, PlaySide, Difficulty)],
+) -> SqlxResult<()> {
+    for (song_id, play_side, difficulty) in diff_ids {
+        sqlx::query(
+            r#"
+            INSERT INTO "list_entries" ("list_id", "song_id", "play_side", "difficulty", "added_at")
+            VALUES (?, ?, ?, ?, strftime('%s', 'now'))
+            ON CONFLICT DO NOTHING;
+            "#,
+        )
+        .bind(list_id)
+        .bind(song_id)
+        .bind(play_side)
+        .bind(difficulty)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Removes the diff from the list. Returns whether it was in the list.
+pub async fn delete_list_entry(
+    pool: &SqlitePool,
+    list_id: i64,
+    (song_id, play_side, difficulty): (i64, PlaySide, Difficulty),
+) -> SqlxResult<bool> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM "list_entries"
+        WHERE "list_id" = ? AND "song_id" = ? AND "play_side" = ? AND "difficulty" = ?;
+        "#,
+    )
+    .bind(list_id)
+    .bind(song_id)
+    .bind(play_side)
+    .bind(difficulty)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
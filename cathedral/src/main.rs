@@ -1,52 +1,193 @@
+mod auth;
+mod catalog;
+mod chat;
 mod cli;
+mod config;
 mod db;
+mod selection;
+mod suggest;
 mod web;
 
 use crate::{
-    cli::Arguments,
-    db::function::{fetch_title_pair, open_sqlite_file},
-    web::action::{mattermost_enqueue, songs_search, songs_show},
+    auth::{BotCommand, TokenRegistry, TokenRegistryHandle},
+    catalog::{spawn_file_reloader, spawn_signal_reloader, CatalogHandle, CatalogLoader},
+    chat::{
+        engine::CommandEngine,
+        render::{Renderer, TableRenderer},
+        schema::CommandContext,
+    },
+    cli::{Arguments, Command, ConfigArguments, OutputFormat, QueryArguments},
+    config::Config,
+    db::userdata::{
+        migrate_userdata, open_userdata_file, open_userdata_memory, userdata_attach_uri,
+    },
+    web::action::{
+        admin_reload, discord_commands, discord_interactions, docs, lists_create, lists_delete,
+        lists_entries_add, lists_entries_remove, lists_index, lists_show, mattermost_action,
+        mattermost_command, mattermost_enqueue, openapi_json, slack_command, slack_events,
+        songs_live, songs_search, songs_show, songs_suggest, stats, users_scores,
+        users_scores_update, versions_index, versions_songs,
+    },
 };
 
-use std::sync::Arc;
+use std::{
+    io::{read_to_string, stderr, stdin},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use axum::{
     routing::{get, post},
     Router, Server,
 };
 use clap::Parser;
+use reqwest::Client as HttpClient;
 use sqlx::SqlitePool;
 
 #[derive(Debug, Clone)]
 pub struct SharedData {
-    webhook_token: String,
-    candidates_count: usize,
-    sqlite_pool: SqlitePool,
-    id_song_pairs: Arc<[(i64, String)]>,
+    arguments: Arc<ConfigArguments>,
+    config: Arc<Config>,
+    tokens: TokenRegistryHandle,
+    catalog: CatalogHandle,
+    catalog_loader: Arc<CatalogLoader>,
+    userdata_pool: SqlitePool,
+    http_client: HttpClient,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Arguments::parse();
-    tracing_subscriber::fmt::init();
+    match args.command {
+        Command::Serve(serve_args) => serve(serve_args).await,
+        Command::Query(query_args) => query(query_args).await,
+    }
+}
+
+/// Opens the user data and prepares the catalog loader.
+async fn open_stores(config: &Config) -> Result<(SqlitePool, CatalogLoader)> {
+    let userdata_filename = config.server.userdata_filename.as_deref();
+    let userdata_pool = match userdata_filename {
+        Some(path) => open_userdata_file(path).await?,
+        None => open_userdata_memory().await?,
+    };
+    migrate_userdata(&userdata_pool).await?;
+
+    let catalog_loader = CatalogLoader {
+        sqlite_filename: config
+            .server
+            .catalog_filename
+            .clone()
+            .expect("should be validated"),
+        userdata_uri: userdata_attach_uri(userdata_filename),
+    };
+    Ok((userdata_pool, catalog_loader))
+}
+
+async fn query(args: QueryArguments) -> Result<()> {
+    let config = Config::load(ConfigArguments {
+        source: args.source,
+        ..Default::default()
+    })?;
+    // stdout is for results
+    tracing_subscriber::fmt()
+        .with_max_level(config.logging.level_filter()?)
+        .with_ansi(config.logging.ansi)
+        .with_writer(stderr)
+        .init();
+
+    let (userdata_pool, catalog_loader) = open_stores(&config).await?;
+    let catalog = catalog_loader.load().await?;
+    let text = if args.queries.is_empty() {
+        read_to_string(stdin())?
+    } else {
+        args.queries.join("\n")
+    };
 
-    let sqlite_pool = open_sqlite_file(&args.sqlite_filename).await?;
-    let id_song_pairs = fetch_title_pair(&sqlite_pool).await?;
+    let engine = CommandEngine {
+        config: &config,
+        catalog: &catalog,
+        userdata_pool: &userdata_pool,
+    };
+    let context = CommandContext {
+        user_name: &args.user,
+        user_id: &args.user,
+        channel_id: &args.channel,
+        commands: BotCommand::ALL,
+    };
+    let result = engine.run(&context, &text).await?;
+    match args.format {
+        OutputFormat::Table => print!("{}", TableRenderer.render(&result)),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+    }
+
+    Ok(())
+}
+
+async fn serve(arguments: ConfigArguments) -> Result<()> {
+    let config = Config::load(arguments.clone())?;
+    tracing_subscriber::fmt()
+        .with_max_level(config.logging.level_filter()?)
+        .with_ansi(config.logging.ansi)
+        .init();
+
+    let (userdata_pool, catalog_loader) = open_stores(&config).await?;
+    let catalog = catalog_loader.load().await?;
+
+    let bind = config.server.bind;
+    let reload_interval = config.server.reload_interval;
+    let tokens = TokenRegistry::new(&config.tokens);
     let shared_data = SharedData {
-        webhook_token: args.mattermost_token,
-        candidates_count: 5,
-        sqlite_pool,
-        id_song_pairs: id_song_pairs.into(),
+        arguments: Arc::new(arguments),
+        config: Arc::new(config),
+        tokens: Arc::new(ArcSwap::from_pointee(tokens)),
+        catalog: Arc::new(ArcSwap::from_pointee(catalog)),
+        catalog_loader: Arc::new(catalog_loader),
+        userdata_pool,
+        http_client: HttpClient::new(),
     };
 
+    spawn_signal_reloader(shared_data.clone())?;
+    if reload_interval > 0 {
+        spawn_file_reloader(shared_data.clone(), Duration::from_secs(reload_interval));
+    }
+
     let router = Router::new()
         .route("/songs/search", get(songs_search))
+        .route("/songs/suggest", get(songs_suggest))
+        .route("/songs/live", get(songs_live))
         .route("/songs/show", get(songs_show))
+        .route(
+            "/users/:user_name/scores",
+            get(users_scores).put(users_scores_update),
+        )
+        .route("/versions", get(versions_index))
+        .route("/versions/:id/songs", get(versions_songs))
+        .route("/stats", get(stats))
+        .route("/lists/:scope/:owner", get(lists_index))
+        .route(
+            "/lists/:scope/:owner/:name",
+            get(lists_show).put(lists_create).delete(lists_delete),
+        )
+        .route(
+            "/lists/:scope/:owner/:name/entries",
+            post(lists_entries_add).delete(lists_entries_remove),
+        )
         .route("/mattermost/enqueue", post(mattermost_enqueue))
+        .route("/mattermost/command", post(mattermost_command))
+        .route("/mattermost/action", post(mattermost_action))
+        .route("/slack/command", post(slack_command))
+        .route("/slack/events", post(slack_events))
+        .route("/discord/interactions", post(discord_interactions))
+        .route("/discord/commands", get(discord_commands))
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(docs))
+        .route("/admin/reload", post(admin_reload))
         .with_state(shared_data);
 
-    Server::bind(&args.bind)
+    Server::bind(&bind)
         .serve(router.into_make_service())
         .await?;
 
@@ -0,0 +1,354 @@
+use crate::db::schema::{DiffCandidate, Difficulty, PlaySide};
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
+use clap::ValueEnum;
+use rand::prelude::*;
+use serde::Deserialize;
+use thiserror::Error as ThisError;
+
+/// Computes selection weights for filtered diff candidates.
+pub trait WeightingStrategy {
+    /// Returns weights for each candidate, in the same order.
+    fn weights(&self, candidates: &[DiffCandidate]) -> Vec<f64>;
+}
+
+/// Every candidate has the same weight.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Uniform;
+
+impl WeightingStrategy for Uniform {
+    fn weights(&self, candidates: &[DiffCandidate]) -> Vec<f64> {
+        vec![1.0; candidates.len()]
+    }
+}
+
+/// Every version has the same total weight regardless of its diff count.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VersionBalanced;
+
+impl WeightingStrategy for VersionBalanced {
+    fn weights(&self, candidates: &[DiffCandidate]) -> Vec<f64> {
+        let mut version_counts: HashMap<i64, usize> = HashMap::new();
+        for candidate in candidates {
+            *version_counts.entry(candidate.version_number).or_default() += 1;
+        }
+
+        candidates
+            .iter()
+            .map(|c| 1.0 / version_counts[&c.version_number] as f64)
+            .collect()
+    }
+}
+
+/// Newer versions have linearly larger weights.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecencyFavored;
+
+impl WeightingStrategy for RecencyFavored {
+    fn weights(&self, candidates: &[DiffCandidate]) -> Vec<f64> {
+        let Some(oldest) = candidates.iter().map(|c| c.version_number).min() else {
+            return vec![];
+        };
+
+        candidates
+            .iter()
+            .map(|c| (c.version_number - oldest + 1) as f64)
+            .collect()
+    }
+}
+
+/// Diffs picked recently have smaller weights.
+#[derive(Debug, Clone, Default)]
+pub struct InverseFrequency {
+    recent_counts: HashMap<(i64, PlaySide, Difficulty), usize>,
+}
+
+impl InverseFrequency {
+    pub fn new<'a>(
+        recent_picks: impl IntoIterator<Item = &'a (i64, PlaySide, Difficulty)>,
+    ) -> Self {
+        let mut recent_counts = HashMap::new();
+        for id in recent_picks {
+            *recent_counts.entry(*id).or_default() += 1;
+        }
+        InverseFrequency { recent_counts }
+    }
+}
+
+impl WeightingStrategy for InverseFrequency {
+    fn weights(&self, candidates: &[DiffCandidate]) -> Vec<f64> {
+        candidates
+            .iter()
+            .map(|c| {
+                let count = self.recent_counts.get(&c.id()).copied().unwrap_or(0);
+                1.0 / (count + 1) as f64
+            })
+            .collect()
+    }
+}
+
+/// Multiplies weights of another strategy by `InverseFrequency`.
+pub struct HistoryPenalty {
+    inner: Box<dyn WeightingStrategy>,
+    penalty: InverseFrequency,
+}
+
+impl HistoryPenalty {
+    pub fn new(
+        inner: Box<dyn WeightingStrategy>,
+        recent_picks: &[(i64, PlaySide, Difficulty)],
+    ) -> HistoryPenalty {
+        HistoryPenalty {
+            inner,
+            penalty: InverseFrequency::new(recent_picks),
+        }
+    }
+}
+
+impl WeightingStrategy for HistoryPenalty {
+    fn weights(&self, candidates: &[DiffCandidate]) -> Vec<f64> {
+        let weights = self.inner.weights(candidates);
+        let penalties = self.penalty.weights(candidates);
+        weights
+            .into_iter()
+            .zip(penalties)
+            .map(|(w, p)| w * p)
+            .collect()
+    }
+}
+
+/// How recently drawn diffs are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryMode {
+    /// Draws regardless of history.
+    Off,
+
+    /// Removes recently drawn diffs from candidates, falling back to the least recently drawn.
+    #[default]
+    Exclude,
+
+    /// Lowers weights of recently drawn diffs.
+    Deweight,
+}
+
+/// Whose recent draws make up the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryScope {
+    /// Draws in the same channel. Requests without channel use the sender's draws without channel.
+    #[default]
+    Channel,
+
+    /// Draws by the same user across all channels.
+    User,
+}
+
+/// Strategy name specified in queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StrategyKind {
+    #[default]
+    Uniform,
+    VersionBalanced,
+    RecencyFavored,
+    InverseFrequency,
+}
+
+impl Display for StrategyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            StrategyKind::Uniform => f.write_str("uniform"),
+            StrategyKind::VersionBalanced => f.write_str("version"),
+            StrategyKind::RecencyFavored => f.write_str("recent"),
+            StrategyKind::InverseFrequency => f.write_str("fresh"),
+        }
+    }
+}
+
+impl StrategyKind {
+    pub fn build(self, recent_picks: &[(i64, PlaySide, Difficulty)]) -> Box<dyn WeightingStrategy> {
+        match self {
+            StrategyKind::Uniform => Box::new(Uniform),
+            StrategyKind::VersionBalanced => Box::new(VersionBalanced),
+            StrategyKind::RecencyFavored => Box::new(RecencyFavored),
+            StrategyKind::InverseFrequency => Box::new(InverseFrequency::new(recent_picks)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[error("unknown weighting strategy: {0}")]
+pub struct UnknownStrategyError(pub String);
+
+impl FromStr for StrategyKind {
+    type Err = UnknownStrategyError;
+
+    fn from_str(s: &str) -> Result<StrategyKind, UnknownStrategyError> {
+        match s {
+            "u" | "uniform" => Ok(StrategyKind::Uniform),
+            "v" | "version" => Ok(StrategyKind::VersionBalanced),
+            "r" | "recent" => Ok(StrategyKind::RecencyFavored),
+            "f" | "fresh" => Ok(StrategyKind::InverseFrequency),
+            _ => Err(UnknownStrategyError(s.into())),
+        }
+    }
+}
+
+/// Chooses at most `count` distinct candidates following the strategy.
+pub fn choose_weighted<'a>(
+    rng: &mut impl Rng,
+    strategy: &dyn WeightingStrategy,
+    candidates: &'a [DiffCandidate],
+    count: usize,
+) -> Vec<&'a DiffCandidate> {
+    let weights = strategy.weights(candidates);
+    let indices: Vec<_> = (0..candidates.len())
+        .filter(|&i| weights[i] > 0.0)
+        .collect();
+    let Ok(chosen) = indices.choose_multiple_weighted(rng, count, |&i| weights[i]) else {
+        return vec![];
+    };
+
+    chosen.map(|&i| &candidates[i]).collect()
+}
+
+/// Removes recently drawn diffs from candidates.
+/// If fewer than `count` remain, fills up with the least recently drawn ones.
+/// `recent_picks` must be ordered latest first.
+pub fn exclude_recent(
+    candidates: Vec<DiffCandidate>,
+    recent_picks: &[(i64, PlaySide, Difficulty)],
+    count: usize,
+) -> Vec<DiffCandidate> {
+    let mut last_drawn = HashMap::new();
+    for (position, id) in recent_picks.iter().enumerate() {
+        last_drawn.entry(*id).or_insert(position);
+    }
+    let (mut fresh, mut stale): (Vec<_>, Vec<_>) = candidates
+        .into_iter()
+        .partition(|c| !last_drawn.contains_key(&c.id()));
+
+    if fresh.len() < count {
+        stale.sort_by_key(|c| Reverse(last_drawn[&c.id()]));
+        fresh.extend(stale.into_iter().take(count - fresh.len()));
+    }
+    fresh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        choose_weighted, exclude_recent, InverseFrequency, RecencyFavored, Uniform,
+        VersionBalanced, WeightingStrategy,
+    };
+    use crate::db::schema::{DiffCandidate, Difficulty, PlaySide};
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn candidate(song_id: i64, version_number: i64) -> DiffCandidate {
+        DiffCandidate {
+            song_id,
+            play_side: PlaySide::Single,
+            difficulty: Difficulty::Another,
+            version_number,
+        }
+    }
+
+    #[test]
+    fn version_balanced_splits_weight_per_version() {
+        let candidates = [candidate(1, 1), candidate(2, 1), candidate(3, 2)];
+        let weights = VersionBalanced.weights(&candidates);
+        assert_eq!(weights, vec![0.5, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn recency_favored_grows_from_oldest_version() {
+        let candidates = [candidate(1, 5), candidate(2, 3), candidate(3, 7)];
+        let weights = RecencyFavored.weights(&candidates);
+        assert_eq!(weights, vec![3.0, 1.0, 5.0]);
+        assert!(RecencyFavored.weights(&[]).is_empty());
+    }
+
+    #[test]
+    fn inverse_frequency_counts_recent_picks() {
+        let candidates = [candidate(1, 1), candidate(2, 1), candidate(3, 1)];
+        let recent_picks = [
+            candidates[0].id(),
+            candidates[0].id(),
+            candidates[1].id(),
+            (1, PlaySide::Double, Difficulty::Another),
+        ];
+        let weights = InverseFrequency::new(&recent_picks).weights(&candidates);
+        assert_eq!(weights, vec![1.0 / 3.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn choose_weighted_returns_distinct_candidates() {
+        let candidates: Vec<_> = (0..10).map(|i| candidate(i, 1)).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut chosen: Vec<_> = choose_weighted(&mut rng, &Uniform, &candidates, 4)
+            .into_iter()
+            .map(|c| c.song_id)
+            .collect();
+        assert_eq!(chosen.len(), 4);
+        chosen.sort_unstable();
+        chosen.dedup();
+        assert_eq!(chosen.len(), 4);
+
+        let all = choose_weighted(&mut rng, &Uniform, &candidates, 20);
+        assert_eq!(all.len(), candidates.len());
+        assert!(choose_weighted(&mut rng, &Uniform, &[], 3).is_empty());
+    }
+
+    #[test]
+    fn choose_weighted_skips_zero_weights() {
+        struct OnlyEven;
+
+        impl WeightingStrategy for OnlyEven {
+            fn weights(&self, candidates: &[DiffCandidate]) -> Vec<f64> {
+                candidates
+                    .iter()
+                    .map(|c| if c.song_id % 2 == 0 { 1.0 } else { 0.0 })
+                    .collect()
+            }
+        }
+
+        let candidates: Vec<_> = (0..10).map(|i| candidate(i, 1)).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+        let chosen = choose_weighted(&mut rng, &OnlyEven, &candidates, 10);
+        assert_eq!(chosen.len(), 5);
+        assert!(chosen.iter().all(|c| c.song_id % 2 == 0));
+    }
+
+    #[test]
+    fn exclude_recent_fills_up_with_least_recently_drawn() {
+        let candidates: Vec<_> = (0..4).map(|i| candidate(i, 1)).collect();
+        let recent_picks = [
+            candidates[1].id(),
+            candidates[2].id(),
+            candidates[1].id(),
+            candidates[3].id(),
+        ];
+
+        let ids = |cs: Vec<DiffCandidate>| cs.into_iter().map(|c| c.song_id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(exclude_recent(candidates.clone(), &recent_picks, 1)),
+            vec![0]
+        );
+        assert_eq!(
+            ids(exclude_recent(candidates.clone(), &recent_picks, 3)),
+            vec![0, 3, 2]
+        );
+        assert_eq!(
+            ids(exclude_recent(candidates[1..].to_vec(), &recent_picks, 1)),
+            vec![3]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::{collections::HashMap, sync::Arc};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Sorted prefix table over normalized titles and aliases of songs.
+#[derive(Debug, Clone, Default)]
+pub struct SuggestIndex {
+    /// Normalized key, index of `songs`, and the original text of the key.
+    entries: Vec<(String, usize, Arc<str>)>,
+    songs: Vec<(i64, Arc<str>)>,
+}
+
+/// Song suggested for a prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion<'a> {
+    pub id: i64,
+    pub title: &'a str,
+    /// Title or alias which matched the prefix.
+    pub matched: &'a str,
+}
+
+impl SuggestIndex {
+    /// Builds the index from `(id, title)` and `(id, alias)` pairs.
+    pub fn new(title_pairs: &[(i64, String)], alias_pairs: &[(i64, String)]) -> SuggestIndex {
+        let songs: Vec<(i64, Arc<str>)> = title_pairs
+            .iter()
+            .map(|(id, title)| (*id, title.as_str().into()))
+            .collect();
+
+        let song_indices: HashMap<_, _> = songs
+            .iter()
+            .enumerate()
+            .map(|(index, (id, _))| (*id, index))
+            .collect();
+
+        let mut entries = vec![];
+        for (index, (_, title)) in songs.iter().enumerate() {
+            entries.push((normalize(title), index, title.clone()));
+        }
+        for (id, alias) in alias_pairs {
+            let Some(&index) = song_indices.get(id) else {
+                continue;
+            };
+            entries.push((normalize(alias), index, alias.as_str().into()));
+        }
+        entries.retain(|(key, _, _)| !key.is_empty());
+        entries.sort_by(|(lk, li, _), (rk, ri, _)| lk.cmp(rk).then(li.cmp(ri)));
+
+        SuggestIndex { entries, songs }
+    }
+
+    /// Returns at most `limit` distinct songs whose title or alias starts with the prefix,
+    /// in order of the normalized keys. Exact matches come first.
+    pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<Suggestion<'_>> {
+        let prefix = normalize(prefix);
+        if prefix.is_empty() || limit == 0 {
+            return vec![];
+        }
+
+        let start = self
+            .entries
+            .partition_point(|(key, _, _)| key.as_str() < prefix.as_str());
+        let matched = self.entries[start..]
+            .iter()
+            .take_while(|(key, _, _)| key.starts_with(&prefix));
+
+        let mut suggestions: Vec<Suggestion> = vec![];
+        for (_, index, text) in matched {
+            let (id, title) = &self.songs[*index];
+            if suggestions.iter().any(|s| s.id == *id) {
+                continue;
+            }
+            suggestions.push(Suggestion {
+                id: *id,
+                title,
+                matched: text,
+            });
+            if suggestions.len() >= limit {
+                break;
+            }
+        }
+
+        suggestions
+    }
+}
+
+/// Folds width, case and kana type, and removes spaces and symbols.
+fn normalize(text: &str) -> String {
+    text.nfkc()
+        .flat_map(char::to_lowercase)
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| match c {
+            // katakana to hiragana
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuggestIndex;
+
+    fn build_index() -> SuggestIndex {
+        let title_pairs = vec![
+            (1, "Fly Above".to_string()),
+            (2, "FLOWER".to_string()),
+            (3, "冥".to_string()),
+            (4, "Flip Flap".to_string()),
+        ];
+        let alias_pairs = vec![
+            (3, "めい".to_string()),
+            (3, "mei".to_string()),
+            (1, "フライアバブ".to_string()),
+            (99, "orphan".to_string()),
+        ];
+        SuggestIndex::new(&title_pairs, &alias_pairs)
+    }
+
+    fn suggested_ids(index: &SuggestIndex, prefix: &str, limit: usize) -> Vec<i64> {
+        index.suggest(prefix, limit).iter().map(|s| s.id).collect()
+    }
+
+    #[test]
+    fn matches_title_prefix() {
+        let index = build_index();
+        assert_eq!(suggested_ids(&index, "fl", 10), vec![4, 2, 1]);
+        assert_eq!(suggested_ids(&index, "Ｆｌｙ　a", 10), vec![1]);
+        assert_eq!(suggested_ids(&index, "冥", 10), vec![3]);
+        assert!(suggested_ids(&index, "zzz", 10).is_empty());
+        assert!(suggested_ids(&index, " ", 10).is_empty());
+    }
+
+    #[test]
+    fn matches_alias_prefix() {
+        let index = build_index();
+
+        let suggestions = index.suggest("メ", 10);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].id, 3);
+        assert_eq!(suggestions[0].title, "冥");
+        assert_eq!(suggestions[0].matched, "めい");
+
+        let suggestions = index.suggest("ふらい", 10);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].title, "Fly Above");
+        assert_eq!(suggestions[0].matched, "フライアバブ");
+
+        assert!(suggested_ids(&index, "orphan", 10).is_empty());
+    }
+
+    #[test]
+    fn stops_at_limit_with_distinct_songs() {
+        let index = build_index();
+        assert_eq!(suggested_ids(&index, "fl", 2), vec![4, 2]);
+        assert!(suggested_ids(&index, "fl", 0).is_empty());
+
+        let index = SuggestIndex::new(&[(1, "Mei".to_string())], &[(1, "mei".to_string())]);
+        assert_eq!(suggested_ids(&index, "me", 10), vec![1]);
+    }
+}
@@ -1,47 +1,243 @@
 use crate::{
-    db::{function::*, schema::*},
-    web::{error::*, schema::*},
+    auth::{reload_tokens, BotCommand, TokenRegistry},
+    catalog::{reload_catalog, Catalog},
+    chat::{
+        engine::{require_command, CommandEngine, Slot},
+        render::{DiscordRenderer, MattermostActions, MattermostRenderer, Renderer, SlackRenderer},
+        schema::{CommandContext, CommandError},
+    },
+    config::SlackConfig,
+    db::{function::*, schema::*, userdata::*},
+    web::{error::*, openapi::ApiDoc, schema::*},
     SharedData,
 };
 
-use std::collections::BinaryHeap;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use anyhow::{bail, Result};
 use axum::{
-    extract::{Query, State},
-    response::Result as AxumResult,
-    Form, Json,
+    body::Bytes,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header::AUTHORIZATION, HeaderMap},
+    response::{Html, Response, Result as AxumResult},
+    Error as AxumError, Form, Json,
+};
+use lyricism::CostProfile;
+use tokio::{
+    sync::mpsc,
+    task::{spawn, spawn_blocking, JoinHandle},
 };
-use lyricism::{query_delete, query_insert, query_replace, query_substring, Lyricism};
-use rand::prelude::*;
 use tracing::warn;
+use utoipa::{openapi::OpenApi as OpenApiDocument, OpenApi};
 
-/// GET /songs/search?q=...
+/// GET /songs/search?q=...&limit=...&offset=...&sort=...&order=...
+#[utoipa::path(
+    get,
+    path = "/songs/search",
+    tag = "songs",
+    params(SongsSearchQuery),
+    responses(
+        (status = 200, description = "Songs in the page", body = [SongsSearchResponse]),
+        (status = 500, description = "Database or filter error", body = ErrorResult),
+    ),
+)]
 pub async fn songs_search(
     State(sd): State<SharedData>,
     Query(query): Query<SongsSearchQuery>,
 ) -> AxumResult<Json<Vec<SongsSearchResponse>>> {
-    let searcher = Lyricism::new(query_insert, query_delete, query_replace, query_substring);
-    let mut candidates = BinaryHeap::new();
+    let catalog = sd.catalog.load_full();
+    let limit = query
+        .limit
+        .unwrap_or(sd.config.search.limit)
+        .min(sd.config.search.max_results);
+    let page = rank_songs(
+        &catalog.id_song_pairs,
+        &sd.config.search.costs,
+        &query,
+        limit,
+        || false,
+    )
+    .unwrap_or_default();
+    let result_rows = fetch_search_results(&catalog, page).await?;
+
+    Ok(Json(result_rows))
+}
+
+/// GET /songs/live (WebSocket)
+pub async fn songs_live(State(sd): State<SharedData>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| live_search_session(sd, socket))
+}
+
+/// Receives queries and sends results until the socket is closed.
+/// A newer query cancels the search of older ones.
+async fn live_search_session(sd: SharedData, mut socket: WebSocket) {
+    let generation = Arc::new(AtomicU64::new(0));
+    let (result_tx, mut result_rx) = mpsc::channel::<LiveSearchResponse>(8);
+    let mut running: Option<JoinHandle<()>> = None;
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let request: LiveSearchRequest = match serde_json::from_str(&text) {
+                    Ok(request) => request,
+                    Err(err) => {
+                        let response = LiveSearchResponse::Error {
+                            id: None,
+                            reason: format!("invalid request: {err}"),
+                        };
+                        if send_live_response(&mut socket, &response).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+
+                let current = generation.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                if let Some(handle) = running.take() {
+                    handle.abort();
+                }
+                running = Some(tokio::spawn(live_search(
+                    sd.clone(),
+                    request,
+                    generation.clone(),
+                    current,
+                    result_tx.clone(),
+                )));
+            }
+            Some(response) = result_rx.recv() => {
+                if send_live_response(&mut socket, &response).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // stops the running search
+    generation.fetch_add(1, AtomicOrdering::SeqCst);
+    if let Some(handle) = running {
+        handle.abort();
+    }
+}
 
-    for (id, title) in &sd.id_song_pairs[..] {
+async fn live_search(
+    sd: SharedData,
+    request: LiveSearchRequest,
+    generation: Arc<AtomicU64>,
+    current: u64,
+    result_tx: mpsc::Sender<LiveSearchResponse>,
+) {
+    let is_stale = {
+        let generation = generation.clone();
+        move || generation.load(AtomicOrdering::SeqCst) != current
+    };
+
+    let catalog = sd.catalog.load_full();
+    let limit = request
+        .query
+        .limit
+        .unwrap_or(sd.config.search.limit)
+        .min(sd.config.search.max_results);
+    let query = request.query;
+    let costs = sd.config.search.costs;
+    let stale = is_stale.clone();
+    let ranking_catalog = catalog.clone();
+    let ranked = spawn_blocking(move || {
+        rank_songs(&ranking_catalog.id_song_pairs, &costs, &query, limit, stale)
+    })
+    .await;
+    let Ok(Some(page)) = ranked else {
+        return;
+    };
+
+    let response = match fetch_search_results(&catalog, page).await {
+        Ok(results) => LiveSearchResponse::Results {
+            id: request.id,
+            results,
+        },
+        Err(_) => LiveSearchResponse::Error {
+            id: Some(request.id),
+            reason: "search failed".into(),
+        },
+    };
+    if !is_stale() {
+        result_tx.send(response).await.ok();
+    }
+}
+
+async fn send_live_response(
+    socket: &mut WebSocket,
+    response: &LiveSearchResponse,
+) -> Result<(), AxumError> {
+    let text = serde_json::to_string(response).expect("should serialize");
+    socket.send(Message::Text(text)).await
+}
+
+/// Ranks songs by the query and returns `(score, id)` in the page.
+/// Returns `None` if cancelled.
+fn rank_songs(
+    id_song_pairs: &[(i64, String)],
+    costs: &CostProfile,
+    query: &SongsSearchQuery,
+    limit: usize,
+    is_cancelled: impl Fn() -> bool,
+) -> Option<Vec<(isize, i64)>> {
+    let searcher = costs.searcher();
+    let mut candidates = Vec::with_capacity(id_song_pairs.len());
+    for (id, title) in id_song_pairs {
+        if is_cancelled() {
+            return None;
+        }
         let distance = searcher.distance(&query.q, title);
-        candidates.push((distance, *id));
-        if candidates.len() > sd.candidates_count {
-            candidates.pop();
+        if query.max_score.map(|m| distance <= m).unwrap_or(true) {
+            candidates.push((distance, *id, title.as_str()));
         }
     }
-    // collect as most-relevant first
-    let mut candidate_pairs: Vec<_> = candidates.into_iter().collect();
-    candidate_pairs.sort_by_key(|(d, _)| *d);
-    let candidate_ids: Vec<_> = candidate_pairs.iter().map(|(_, id)| *id).collect();
 
-    let rows = fetch_songs_with_versions(&sd.sqlite_pool, &candidate_ids)
+    match query.sort {
+        SearchSort::Score => candidates.sort_by_key(|&(d, id, _)| (d, id)),
+        SearchSort::Title => candidates.sort_by_key(|&(_, id, t)| (t, id)),
+    }
+    if query.order == SortOrder::Desc {
+        candidates.reverse();
+    }
+
+    let page = candidates
+        .into_iter()
+        .skip(query.offset)
+        .take(limit)
+        .map(|(d, id, _)| (d, id))
+        .collect();
+    Some(page)
+}
+
+async fn fetch_search_results(
+    catalog: &Catalog,
+    page: Vec<(isize, i64)>,
+) -> AxumResult<Vec<SongsSearchResponse>> {
+    let page_ids: Vec<_> = page.iter().map(|(_, id)| *id).collect();
+    let rows = fetch_songs_with_versions(&catalog.sqlite_pool, &page_ids)
         .await
         .map_err(pass_sqlx_error)?;
-    let result_rows = candidate_ids
+
+    let result_rows = page
         .into_iter()
-        .flat_map(|cid| rows.iter().find(|(s, _)| s.id == cid))
-        .map(|(s, v)| SongsSearchResponse {
+        .flat_map(|(d, cid)| rows.iter().find(|(s, _)| s.id == cid).map(|r| (d, r)))
+        .map(|(d, (s, v))| SongsSearchResponse {
+            score: d,
             version_abbrev: v.abbrev.to_string(),
             id: s.id,
             genre: s.genre.to_string(),
@@ -49,24 +245,545 @@ pub async fn songs_search(
             artist: s.artist.to_string(),
         })
         .collect();
+    Ok(result_rows)
+}
 
-    Ok(Json(result_rows))
+/// GET /songs/suggest?prefix=...&limit=...
+#[utoipa::path(
+    get,
+    path = "/songs/suggest",
+    tag = "songs",
+    params(SongsSuggestQuery),
+    responses((status = 200, description = "Songs whose title or alias starts with the prefix", body = [SongsSuggestResponse])),
+)]
+pub async fn songs_suggest(
+    State(sd): State<SharedData>,
+    Query(query): Query<SongsSuggestQuery>,
+) -> Json<Vec<SongsSuggestResponse>> {
+    let catalog = sd.catalog.load_full();
+    let limit = query
+        .limit
+        .unwrap_or(sd.config.search.limit)
+        .min(sd.config.search.max_results);
+    let suggestions = catalog
+        .suggest_index
+        .suggest(&query.prefix, limit)
+        .into_iter()
+        .map(|s| SongsSuggestResponse {
+            id: s.id,
+            title: s.title.to_string(),
+            matched: s.matched.to_string(),
+        })
+        .collect();
+
+    Json(suggestions)
 }
 
 /// GET /songs/show?id=...
+#[utoipa::path(
+    get,
+    path = "/songs/show",
+    tag = "songs",
+    params(SongsShowQuery),
+    responses(
+        (status = 200, description = "Song with its diffs", body = SongsShowResponse),
+        (status = 404, description = "Not found", body = ErrorResult),
+        (status = 500, description = "Database or filter error", body = ErrorResult),
+    ),
+)]
 pub async fn songs_show(
     State(sd): State<SharedData>,
     Query(query): Query<SongsShowQuery>,
 ) -> AxumResult<Json<SongsShowResponse>> {
-    let song = fetch_song(&sd.sqlite_pool, query.id)
+    let catalog = sd.catalog.load_full();
+    let song = fetch_song(&catalog.sqlite_pool, query.id)
         .await
         .map_err(pass_sqlx_error)?
         .ok_or_else(|| pass_not_found_error(&format!("song id {}", query.id)))?;
-    let diffs = fetch_diffs_by_song_ids(&sd.sqlite_pool, &[song.id])
+    let diffs = fetch_diffs_by_song_ids(&catalog.sqlite_pool, &[song.id])
+        .await
+        .map_err(pass_sqlx_error)?;
+    let tiers = fetch_tiers_by_song_ids(&catalog.sqlite_pool, &[song.id])
+        .await
+        .map_err(pass_sqlx_error)?;
+    let availabilities = fetch_availabilities_by_song_ids(&catalog.sqlite_pool, &[song.id])
+        .await
+        .map_err(pass_sqlx_error)?;
+
+    Ok(Json(SongsShowResponse {
+        song,
+        diffs,
+        tiers,
+        availabilities,
+    }))
+}
+
+/// GET /versions
+#[utoipa::path(
+    get,
+    path = "/versions",
+    tag = "versions",
+    responses(
+        (status = 200, description = "All versions", body = [Version]),
+        (status = 500, description = "Database or filter error", body = ErrorResult),
+    ),
+)]
+pub async fn versions_index(State(sd): State<SharedData>) -> AxumResult<Json<Vec<Version>>> {
+    let catalog = sd.catalog.load_full();
+    let versions = fetch_versions(&catalog.sqlite_pool)
+        .await
+        .map_err(pass_sqlx_error)?;
+
+    Ok(Json(versions))
+}
+
+/// GET /versions/{id}/songs?sort=...&order=...&limit=...&offset=...
+#[utoipa::path(
+    get,
+    path = "/versions/{id}/songs",
+    tag = "versions",
+    params(("id" = i64, Path, description = "Version ID"), VersionsSongsQuery),
+    responses(
+        (status = 200, description = "Songs in the page", body = VersionsSongsResponse),
+        (status = 404, description = "Not found", body = ErrorResult),
+        (status = 500, description = "Database or filter error", body = ErrorResult),
+    ),
+)]
+pub async fn versions_songs(
+    State(sd): State<SharedData>,
+    Path(id): Path<i64>,
+    Query(query): Query<VersionsSongsQuery>,
+) -> AxumResult<Json<VersionsSongsResponse>> {
+    let catalog = sd.catalog.load_full();
+    let version = fetch_version(&catalog.sqlite_pool, id)
+        .await
+        .map_err(pass_sqlx_error)?
+        .ok_or_else(|| pass_not_found_error(&format!("version id {id}")))?;
+
+    let limit = query
+        .limit
+        .unwrap_or(sd.config.search.limit)
+        .min(sd.config.search.max_results);
+    let (songs, total) = fetch_songs_by_version(
+        &catalog.sqlite_pool,
+        version.id,
+        query.sort,
+        query.order,
+        limit,
+        query.offset,
+    )
+    .await
+    .map_err(pass_sqlx_error)?;
+
+    Ok(Json(VersionsSongsResponse {
+        version,
+        total,
+        songs,
+    }))
+}
+
+/// GET /stats?group=...&q=...
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "stats",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Diff counts by group", body = StatsResponse),
+        (status = 500, description = "Database or filter error", body = ErrorResult),
+    ),
+)]
+pub async fn stats(
+    State(sd): State<SharedData>,
+    Query(query): Query<StatsQuery>,
+) -> AxumResult<Json<StatsResponse>> {
+    let catalog = sd.catalog.load_full();
+    let group: StatsGroup = query.group.parse().map_err(pass_filter_query_error)?;
+    let filters = query
+        .q
+        .split_ascii_whitespace()
+        .map(|q| q.parse())
+        .collect::<Result<Vec<FilterQuery>, _>>()
+        .map_err(pass_filter_query_error)?;
+    let context = FilterContext {
+        user_name: &query.user_name,
+        ..Default::default()
+    };
+
+    let counts = query_diff_stats(&catalog.sqlite_pool, &filters, context, group)
+        .await
+        .map_err(pass_sqlx_error)?;
+    let total = counts.iter().map(|c| c.count).sum();
+
+    Ok(Json(StatsResponse { total, counts }))
+}
+
+/// GET /users/{user_name}/scores?song_id=...
+#[utoipa::path(
+    get,
+    path = "/users/{user_name}/scores",
+    tag = "users",
+    params(("user_name" = String, Path, description = "User name"), UsersScoresQuery),
+    responses(
+        (status = 200, description = "Scores of the user", body = [Score]),
+        (status = 500, description = "Database error", body = ErrorResult),
+    ),
+)]
+pub async fn users_scores(
+    State(sd): State<SharedData>,
+    Path(user_name): Path<String>,
+    Query(query): Query<UsersScoresQuery>,
+) -> AxumResult<Json<Vec<Score>>> {
+    let scores = fetch_scores(&sd.userdata_pool, &user_name, query.song_id)
+        .await
+        .map_err(pass_sqlx_error)?;
+
+    Ok(Json(scores))
+}
+
+/// PUT /users/{user_name}/scores
+#[utoipa::path(
+    put,
+    path = "/users/{user_name}/scores",
+    tag = "users",
+    params(("user_name" = String, Path, description = "User name")),
+    request_body = ScoreUpdate,
+    responses(
+        (status = 200, description = "Updated score", body = Score),
+        (status = 401, description = "Invalid admin token", body = ErrorResult),
+        (status = 404, description = "Not found", body = ErrorResult),
+        (status = 500, description = "Database error", body = ErrorResult),
+    ),
+    security(("admin_token" = [])),
+)]
+pub async fn users_scores_update(
+    State(sd): State<SharedData>,
+    Path(user_name): Path<String>,
+    headers: HeaderMap,
+    Json(update): Json<ScoreUpdate>,
+) -> AxumResult<Json<Score>> {
+    if !authorize_admin(&sd, &headers) {
+        return Err(pass_admin_error());
+    }
+
+    let catalog = sd.catalog.load_full();
+    let diffs = fetch_diffs_by_ids(
+        &catalog.sqlite_pool,
+        &[(update.song_id, update.play_side, update.difficulty)],
+    )
+    .await
+    .map_err(pass_sqlx_error)?;
+    if diffs.is_empty() {
+        return Err(pass_not_found_error(&format!(
+            "diff {} {} {}",
+            update.song_id, update.play_side, update.difficulty
+        )));
+    }
+
+    let score = upsert_score(&sd.userdata_pool, &user_name, &update)
+        .await
+        .map_err(pass_sqlx_error)?;
+
+    Ok(Json(score))
+}
+
+/// GET /lists/{scope}/{owner}
+#[utoipa::path(
+    get,
+    path = "/lists/{scope}/{owner}",
+    tag = "lists",
+    params(
+        ("scope" = ListScope, Path, description = "Whether the owner is a user or a channel"),
+        ("owner" = String, Path, description = "User name or channel ID"),
+    ),
+    responses(
+        (status = 200, description = "Lists of the owner", body = [DiffList]),
+        (status = 500, description = "Database error", body = ErrorResult),
+    ),
+)]
+pub async fn lists_index(
+    State(sd): State<SharedData>,
+    Path((scope, owner)): Path<(ListScope, String)>,
+) -> AxumResult<Json<Vec<DiffList>>> {
+    let lists = fetch_lists(&sd.userdata_pool, scope, &owner)
         .await
         .map_err(pass_sqlx_error)?;
 
-    Ok(Json(SongsShowResponse { song, diffs }))
+    Ok(Json(lists))
+}
+
+/// GET /lists/{scope}/{owner}/{name}
+#[utoipa::path(
+    get,
+    path = "/lists/{scope}/{owner}/{name}",
+    tag = "lists",
+    params(
+        ("scope" = ListScope, Path, description = "Whether the owner is a user or a channel"),
+        ("owner" = String, Path, description = "User name or channel ID"),
+        ("name" = String, Path, description = "List name"),
+    ),
+    responses(
+        (status = 200, description = "List with its diffs", body = ListsShowResponse),
+        (status = 404, description = "Not found", body = ErrorResult),
+        (status = 500, description = "Database error", body = ErrorResult),
+    ),
+)]
+pub async fn lists_show(
+    State(sd): State<SharedData>,
+    Path((scope, owner, name)): Path<(ListScope, String, String)>,
+) -> AxumResult<Json<ListsShowResponse>> {
+    let catalog = sd.catalog.load_full();
+    let list = find_list(&sd, scope, &owner, &name).await?;
+    let response = list_with_diffs(&sd, &catalog, list).await?;
+
+    Ok(Json(response))
+}
+
+/// PUT /lists/{scope}/{owner}/{name}
+#[utoipa::path(
+    put,
+    path = "/lists/{scope}/{owner}/{name}",
+    tag = "lists",
+    params(
+        ("scope" = ListScope, Path, description = "Whether the owner is a user or a channel"),
+        ("owner" = String, Path, description = "User name or channel ID"),
+        ("name" = String, Path, description = "List name"),
+    ),
+    responses(
+        (status = 200, description = "Created or existing list", body = DiffList),
+        (status = 401, description = "Invalid admin token", body = ErrorResult),
+        (status = 500, description = "Database error", body = ErrorResult),
+    ),
+    security(("admin_token" = [])),
+)]
+pub async fn lists_create(
+    State(sd): State<SharedData>,
+    Path((scope, owner, name)): Path<(ListScope, String, String)>,
+    headers: HeaderMap,
+) -> AxumResult<Json<DiffList>> {
+    if !authorize_admin(&sd, &headers) {
+        return Err(pass_admin_error());
+    }
+
+    let list = create_list(&sd.userdata_pool, scope, &owner, &name)
+        .await
+        .map_err(pass_sqlx_error)?;
+
+    Ok(Json(list))
+}
+
+/// DELETE /lists/{scope}/{owner}/{name}
+#[utoipa::path(
+    delete,
+    path = "/lists/{scope}/{owner}/{name}",
+    tag = "lists",
+    params(
+        ("scope" = ListScope, Path, description = "Whether the owner is a user or a channel"),
+        ("owner" = String, Path, description = "User name or channel ID"),
+        ("name" = String, Path, description = "List name"),
+    ),
+    responses(
+        (status = 200, description = "Deleted list", body = DiffList),
+        (status = 401, description = "Invalid admin token", body = ErrorResult),
+        (status = 404, description = "Not found", body = ErrorResult),
+        (status = 500, description = "Database error", body = ErrorResult),
+    ),
+    security(("admin_token" = [])),
+)]
+pub async fn lists_delete(
+    State(sd): State<SharedData>,
+    Path((scope, owner, name)): Path<(ListScope, String, String)>,
+    headers: HeaderMap,
+) -> AxumResult<Json<DiffList>> {
+    if !authorize_admin(&sd, &headers) {
+        return Err(pass_admin_error());
+    }
+
+    let list = find_list(&sd, scope, &owner, &name).await?;
+    delete_list(&sd.userdata_pool, list.id)
+        .await
+        .map_err(pass_sqlx_error)?;
+
+    Ok(Json(list))
+}
+
+/// POST /lists/{scope}/{owner}/{name}/entries
+#[utoipa::path(
+    post,
+    path = "/lists/{scope}/{owner}/{name}/entries",
+    tag = "lists",
+    params(
+        ("scope" = ListScope, Path, description = "Whether the owner is a user or a channel"),
+        ("owner" = String, Path, description = "User name or channel ID"),
+        ("name" = String, Path, description = "List name"),
+    ),
+    request_body = ListEntryQuery,
+    responses(
+        (status = 200, description = "List with its diffs", body = ListsShowResponse),
+        (status = 401, description = "Invalid admin token", body = ErrorResult),
+        (status = 404, description = "Not found", body = ErrorResult),
+        (status = 500, description = "Database error", body = ErrorResult),
+    ),
+    security(("admin_token" = [])),
+)]
+pub async fn lists_entries_add(
+    State(sd): State<SharedData>,
+    Path((scope, owner, name)): Path<(ListScope, String, String)>,
+    headers: HeaderMap,
+    Json(entry): Json<ListEntryQuery>,
+) -> AxumResult<Json<ListsShowResponse>> {
+    if !authorize_admin(&sd, &headers) {
+        return Err(pass_admin_error());
+    }
+
+    let catalog = sd.catalog.load_full();
+    let list = find_list(&sd, scope, &owner, &name).await?;
+    let diff_id = (entry.song_id, entry.play_side, entry.difficulty);
+    let diffs = fetch_diffs_by_ids(&catalog.sqlite_pool, &[diff_id])
+        .await
+        .map_err(pass_sqlx_error)?;
+    if diffs.is_empty() {
+        return Err(pass_not_found_error(&format!(
+            "diff {} {} {}",
+            entry.song_id, entry.play_side, entry.difficulty
+        )));
+    }
+
+    insert_list_entries(&sd.userdata_pool, list.id, &[diff_id])
+        .await
+        .map_err(pass_sqlx_error)?;
+    let response = list_with_diffs(&sd, &catalog, list).await?;
+
+    Ok(Json(response))
+}
+
+/// DELETE /lists/{scope}/{owner}/{name}/entries?song_id=...&play_side=...&difficulty=...
+#[utoipa::path(
+    delete,
+    path = "/lists/{scope}/{owner}/{name}/entries",
+    tag = "lists",
+    params(
+        ("scope" = ListScope, Path, description = "Whether the owner is a user or a channel"),
+        ("owner" = String, Path, description = "User name or channel ID"),
+        ("name" = String, Path, description = "List name"),
+        ListEntryQuery,
+    ),
+    responses(
+        (status = 200, description = "List with its diffs", body = ListsShowResponse),
+        (status = 401, description = "Invalid admin token", body = ErrorResult),
+        (status = 404, description = "Not found", body = ErrorResult),
+        (status = 500, description = "Database error", body = ErrorResult),
+    ),
+    security(("admin_token" = [])),
+)]
+pub async fn lists_entries_remove(
+    State(sd): State<SharedData>,
+    Path((scope, owner, name)): Path<(ListScope, String, String)>,
+    headers: HeaderMap,
+    Query(entry): Query<ListEntryQuery>,
+) -> AxumResult<Json<ListsShowResponse>> {
+    if !authorize_admin(&sd, &headers) {
+        return Err(pass_admin_error());
+    }
+
+    let catalog = sd.catalog.load_full();
+    let list = find_list(&sd, scope, &owner, &name).await?;
+    let diff_id = (entry.song_id, entry.play_side, entry.difficulty);
+    let removed = delete_list_entry(&sd.userdata_pool, list.id, diff_id)
+        .await
+        .map_err(pass_sqlx_error)?;
+    if !removed {
+        return Err(pass_not_found_error(&format!(
+            "diff {} {} {} in list {}",
+            entry.song_id, entry.play_side, entry.difficulty, list.name
+        )));
+    }
+
+    let response = list_with_diffs(&sd, &catalog, list).await?;
+
+    Ok(Json(response))
+}
+
+async fn find_list(
+    sd: &SharedData,
+    scope: ListScope,
+    owner: &str,
+    name: &str,
+) -> AxumResult<DiffList> {
+    let list = fetch_list(&sd.userdata_pool, scope, owner, name)
+        .await
+        .map_err(pass_sqlx_error)?
+        .ok_or_else(|| pass_not_found_error(&format!("list {name}")))?;
+
+    Ok(list)
+}
+
+async fn list_with_diffs(
+    sd: &SharedData,
+    catalog: &Catalog,
+    list: DiffList,
+) -> AxumResult<ListsShowResponse> {
+    let entries = fetch_list_entries(&sd.userdata_pool, list.id)
+        .await
+        .map_err(pass_sqlx_error)?;
+    let diffs = fetch_diffs_by_ids(&catalog.sqlite_pool, &entries)
+        .await
+        .map_err(pass_sqlx_error)?;
+
+    Ok(ListsShowResponse { list, diffs })
+}
+
+/// GET /openapi.json
+pub async fn openapi_json() -> Json<OpenApiDocument> {
+    Json(ApiDoc::openapi())
+}
+
+/// GET /docs
+pub async fn docs() -> Html<&'static str> {
+    Html(include_str!("docs.html"))
+}
+
+/// POST /admin/reload
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Reloaded catalog and tokens", body = AdminReloadResult),
+        (status = 401, description = "Invalid admin token", body = ErrorResult),
+        (status = 500, description = "Reload error", body = ErrorResult),
+    ),
+    security(("admin_token" = [])),
+)]
+pub async fn admin_reload(
+    State(sd): State<SharedData>,
+    headers: HeaderMap,
+) -> AxumResult<Json<AdminReloadResult>> {
+    if !authorize_admin(&sd, &headers) {
+        return Err(pass_admin_error());
+    }
+
+    let songs_count = reload_catalog(&sd).await.map_err(pass_reload_error)?;
+    let webhooks_count = reload_tokens(&sd).map_err(pass_reload_error)?;
+
+    Ok(Json(AdminReloadResult {
+        songs_count,
+        webhooks_count,
+    }))
+}
+
+/// Checks `Authorization: Bearer <admin token>` of the request.
+fn authorize_admin(sd: &SharedData, headers: &HeaderMap) -> bool {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let authorized = sd.tokens.load().authorize_admin(bearer);
+    if !authorized {
+        warn!("Invalid admin token arrived");
+    }
+
+    authorized
 }
 
 /// GET /mattermost/enqueue
@@ -74,232 +791,502 @@ pub async fn mattermost_enqueue(
     State(sd): State<SharedData>,
     Form(form): Form<MattermostEnqueueForm>,
 ) -> AxumResult<Json<Option<MattermostEnqueueResult>>> {
-    if sd.webhook_token != form.token {
+    let catalog = sd.catalog.load_full();
+    let tokens = sd.tokens.load();
+    let Some(webhook) = tokens.authorize_webhook(&form.token, &form.team_id, &form.channel_id)
+    else {
         warn!("Invalid token arrived");
         return Err(pass_token_error());
-    }
+    };
     if form.text.starts_with("//") {
         return Ok(Json(None));
     }
 
-    let searcher = Lyricism::new(query_insert, query_delete, query_replace, query_substring);
-    let queries = form
-        .text
-        .split('\n')
-        .map(|q| q.trim())
-        .filter(|q| !q.is_empty());
-
-    let mut song_ids = vec![];
-    let mut diff_ids = vec![];
-    for query in queries {
-        if let Some(filters_str) = query.strip_prefix('?') {
-            // diff filter query
-            let (filters, count) =
-                parse_extended_query(filters_str).map_err(pass_filter_query_error)?;
-            let filtered_ids = query_filter_diffs(&sd.sqlite_pool, &filters)
-                .await
-                .map_err(pass_sqlx_error)?;
-
-            let mut rng = thread_rng();
-            let chosen_ids: Vec<_> = filtered_ids
-                .choose_multiple(&mut rng, count)
-                .copied()
-                .collect();
-            diff_ids.extend_from_slice(&chosen_ids);
-        } else if let Some(compact_str) = query.strip_prefix('!') {
-            // diff compact query
-            let compact_queries = parse_compact_queries(compact_str);
-
-            for (query, count) in compact_queries {
-                let filtered_ids = query_filter_diffs(&sd.sqlite_pool, &query)
-                    .await
-                    .map_err(pass_sqlx_error)?;
-
-                let mut rng = thread_rng();
-                let chosen_ids: Vec<_> = filtered_ids
-                    .choose_multiple(&mut rng, count)
-                    .copied()
-                    .collect();
-                diff_ids.extend_from_slice(&chosen_ids);
-            }
-        } else {
-            // song title
-            let mut candidate_distasnce = isize::MAX;
-            let mut candidate_id = 0;
-
-            for (id, title) in &sd.id_song_pairs[..] {
-                let distance = searcher.distance(query, title);
-                if distance < candidate_distasnce {
-                    candidate_distasnce = distance;
-                    candidate_id = *id;
-                }
-            }
+    let engine = CommandEngine {
+        config: &sd.config,
+        catalog: &catalog,
+        userdata_pool: &sd.userdata_pool,
+    };
+    let context = CommandContext {
+        user_name: &form.user_name,
+        user_id: &form.user_id,
+        channel_id: &form.channel_id,
+        commands: &webhook.commands,
+    };
+    let result = engine
+        .run(&context, &form.text)
+        .await
+        .map_err(pass_command_error)?;
+    let renderer = MattermostRenderer {
+        username: &sd.config.bot.username,
+        emoji: &sd.config.bot.emoji,
+        actions: sd
+            .config
+            .bot
+            .action_url
+            .as_deref()
+            .map(|url| MattermostActions {
+                url,
+                token: &form.token,
+            }),
+    };
+
+    Ok(Json(Some(renderer.render(&result))))
+}
 
-            song_ids.push(candidate_id);
-        };
+/// POST /mattermost/command
+pub async fn mattermost_command(
+    State(sd): State<SharedData>,
+    Form(form): Form<MattermostCommandForm>,
+) -> AxumResult<Json<MattermostEnqueueResult>> {
+    let catalog = sd.catalog.load_full();
+    let tokens = sd.tokens.load();
+    let Some(webhook) = tokens.authorize_webhook(&form.token, &form.team_id, &form.channel_id)
+    else {
+        warn!("Invalid token arrived");
+        return Err(pass_token_error());
+    };
+    let renderer = MattermostRenderer {
+        username: &sd.config.bot.username,
+        emoji: &sd.config.bot.emoji,
+        actions: sd
+            .config
+            .bot
+            .action_url
+            .as_deref()
+            .map(|url| MattermostActions {
+                url,
+                token: &form.token,
+            }),
+    };
+    let text = form.text.trim();
+    if text.is_empty() || text == "help" {
+        return Ok(Json(renderer.help(&form.command)));
     }
 
-    let by_diff_diffs = fetch_diffs_by_ids(&sd.sqlite_pool, &diff_ids)
-        .await
-        .map_err(pass_sqlx_error)?;
-    let by_song_diffs = fetch_diffs_by_song_ids(&sd.sqlite_pool, &song_ids)
-        .await
-        .map_err(pass_sqlx_error)?;
+    let engine = CommandEngine {
+        config: &sd.config,
+        catalog: &catalog,
+        userdata_pool: &sd.userdata_pool,
+    };
+    let context = CommandContext {
+        user_name: &form.user_name,
+        user_id: &form.user_id,
+        channel_id: &form.channel_id,
+        commands: &webhook.commands,
+    };
+    let message = match engine.run(&context, text).await {
+        Ok(result) => MattermostEnqueueResult {
+            response_type: Some(webhook.response_type),
+            ..renderer.render(&result)
+        },
+        // Mattermost shows only a generic failure for non-200 responses
+        Err(err) => renderer.error(&form.command, text, &err),
+    };
 
-    let merged_song_ids: Vec<_> = song_ids
+    Ok(Json(message))
+}
+
+/// POST /mattermost/action
+pub async fn mattermost_action(
+    State(sd): State<SharedData>,
+    Json(request): Json<MattermostActionRequest>,
+) -> AxumResult<Json<MattermostActionResponse>> {
+    let catalog = sd.catalog.load_full();
+    let tokens = sd.tokens.load();
+    let action = &request.context;
+    let Some(webhook) =
+        tokens.authorize_webhook(&action.token, &request.team_id, &request.channel_id)
+    else {
+        warn!("Invalid token arrived");
+        return Err(pass_token_error());
+    };
+
+    let engine = CommandEngine {
+        config: &sd.config,
+        catalog: &catalog,
+        userdata_pool: &sd.userdata_pool,
+    };
+    let context = CommandContext {
+        user_name: &request.user_name,
+        user_id: &request.user_id,
+        channel_id: &request.channel_id,
+        commands: &webhook.commands,
+    };
+    let mut slots: Vec<Slot> = action
+        .slots
         .iter()
-        .copied()
-        .chain(by_diff_diffs.iter().map(|d| d.song_id))
+        .map(|s| ((s.song_id, s.play_side, s.difficulty), s.redraw.clone()))
         .collect();
-    let song_version_pairs = fetch_songs_with_versions(&sd.sqlite_pool, &merged_song_ids)
-        .await
-        .map_err(pass_sqlx_error)?;
-
-    // by_song_diffs have all diffs which correspond to songs in song_ids,
-    // so merged_song_ids don't have to care about it.
-
-    let mut texts = vec![];
-    for diff in by_diff_diffs {
-        let Some((song, version)) = song_version_pairs.iter().find(|(s, _)| s.id == diff.song_id) else {
-            continue;
-        };
-        texts.push(format!(
-            "* [{} {} :level-{}:] **{}** ({})",
-            diff.play_side,
-            diff.difficulty.to_emoji_str(),
-            diff.level,
-            song.title,
-            version.abbrev
-        ));
-    }
+    let mut song_ids = action.song_ids.clone();
+    let acted = match action.action {
+        MattermostAction::Reroll => engine.redraw_slot(&context, &mut slots, action.slot).await,
+        MattermostAction::Show => require_command(&context, BotCommand::Search).and_then(|_| {
+            let (song_id, _, _) = slots
+                .get(action.slot)
+                .ok_or_else(|| CommandError::NotFound(format!("slot {}", action.slot)))?
+                .0;
+            if !song_ids.contains(&song_id) {
+                song_ids.push(song_id);
+            }
+            Ok(())
+        }),
+    };
+    let result = match acted {
+        Ok(()) => engine.rebuild(slots, song_ids).await,
+        Err(err) => Err(err),
+    };
 
-    let mut attachments = vec![];
-    for song_id in song_ids {
-        let Some((song, version)) = song_version_pairs.iter().find(|(s, _)| s.id == song_id) else {
-            continue;
-        };
-
-        let song_diffs = by_song_diffs.iter().filter(|d| d.song_id == song.id);
-        let sp_diffs: Vec<_> = song_diffs
-            .clone()
-            .filter(|d| d.play_side == PlaySide::Single)
-            .map(|d| format!("{} :level-{}:", d.difficulty.to_emoji_str(), d.level))
-            .collect();
-        let dp_diffs: Vec<_> = song_diffs
-            .clone()
-            .filter(|d| d.play_side == PlaySide::Single)
-            .map(|d| format!("{} :level-{}:", d.difficulty.to_emoji_str(), d.level))
-            .collect();
-
-        attachments.push(AttachmentSongInfo {
-            title: format!("{} / {}", song.title, song.artist),
-            footer: version.name.clone(),
-            fields: vec![
-                AttachmentSongField {
-                    short: true,
-                    title: "SP Levels".into(),
-                    value: sp_diffs.join(" / "),
-                },
-                AttachmentSongField {
-                    short: true,
-                    title: "DP Levels".into(),
-                    value: dp_diffs.join(" / "),
-                },
-                AttachmentSongField {
-                    short: true,
-                    title: "BPM".into(),
-                    value: if let Some(min_bpm) = song.min_bpm {
-                        format!("{min_bpm} - {}", song.max_bpm)
-                    } else {
-                        song.max_bpm.to_string()
+    let response = match result {
+        Ok(result) => {
+            let message = MattermostRenderer {
+                username: &sd.config.bot.username,
+                emoji: &sd.config.bot.emoji,
+                actions: sd
+                    .config
+                    .bot
+                    .action_url
+                    .as_deref()
+                    .map(|url| MattermostActions {
+                        url,
+                        token: &action.token,
+                    }),
+            }
+            .render(&result);
+            MattermostActionResponse {
+                update: Some(MattermostPostUpdate {
+                    message: message.text,
+                    props: MattermostPostProps {
+                        attachments: message.attachments,
                     },
-                },
+                }),
+                ephemeral_text: None,
+            }
+        }
+        // Mattermost shows only a generic failure for non-200 responses
+        Err(err) => MattermostActionResponse {
+            update: None,
+            ephemeral_text: Some(format!("{:?} #{}: {err}", action.action, action.slot + 1)),
+        },
+    };
+
+    Ok(Json(response))
+}
+
+/// POST /discord/interactions
+pub async fn discord_interactions(
+    State(sd): State<SharedData>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AxumResult<Json<DiscordInteractionResponse>> {
+    let timestamp = headers
+        .get("x-signature-timestamp")
+        .and_then(|v| v.to_str().ok());
+    let signature = headers
+        .get("x-signature-ed25519")
+        .and_then(|v| v.to_str().ok());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let catalog = sd.catalog.load_full();
+    let tokens = sd.tokens.load();
+    // Discord also sends invalid signatures on registration, expecting 401
+    let Some(discord) = tokens.authorize_discord(timestamp, signature, &body, now) else {
+        warn!("Invalid Discord signature arrived");
+        return Err(pass_token_error());
+    };
+    let interaction: DiscordInteraction = serde_json::from_slice(&body).map_err(pass_json_error)?;
+
+    let data = match (interaction.kind, &interaction.data) {
+        (DiscordInteractionType::Ping, _) => {
+            return Ok(Json(DiscordInteractionResponse {
+                kind: DiscordResponseType::Pong,
+                data: None,
+            }));
+        }
+        (DiscordInteractionType::ApplicationCommand, Some(data)) => data,
+        _ => return Err(pass_not_found_error("interaction type")),
+    };
+    let (user_name, user_id) = interaction
+        .sender()
+        .map(|u| (u.username.as_str(), u.id.as_str()))
+        .unwrap_or_default();
+
+    let engine = CommandEngine {
+        config: &sd.config,
+        catalog: &catalog,
+        userdata_pool: &sd.userdata_pool,
+    };
+    let context = CommandContext {
+        user_name,
+        user_id,
+        channel_id: interaction.channel_id.as_deref().unwrap_or_default(),
+        commands: &discord.commands,
+    };
+    let option_str = |name| {
+        data.option(name)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+    };
+    let option_int = |name| data.option(name).and_then(|v| v.as_i64());
+    let result = match data.name.as_str() {
+        "search" => engine.search_title(&context, option_str("title")).await,
+        "random" => {
+            let count = option_int("count").unwrap_or(1).max(1) as usize;
+            engine
+                .draw_filtered(&context, option_str("filters"), count)
+                .await
+        }
+        "show" => match option_int("id") {
+            Some(song_id) => engine.show_song(&context, song_id).await,
+            None => Err(CommandError::NotFound("song id".into())),
+        },
+        name => Err(CommandError::NotFound(format!("command {name}"))),
+    };
+    let message = match result {
+        Ok(result) => DiscordRenderer.render(&result),
+        // Discord shows only a generic failure for non-200 responses
+        Err(err) => DiscordMessage {
+            content: format!("/{}: {err}", data.name),
+            embeds: vec![],
+            flags: Some(DISCORD_EPHEMERAL),
+        },
+    };
+
+    Ok(Json(DiscordInteractionResponse {
+        kind: DiscordResponseType::ChannelMessageWithSource,
+        data: Some(message),
+    }))
+}
+
+/// GET /discord/commands
+/// Application commands to register with `PUT /applications/<id>/commands` of the Discord API.
+pub async fn discord_commands() -> Json<Vec<DiscordCommandSchema>> {
+    let option = |kind, name, description, required| DiscordCommandOption {
+        kind,
+        name,
+        description,
+        required,
+    };
+    Json(vec![
+        DiscordCommandSchema {
+            name: "search",
+            description: "Searches a song by its title",
+            options: vec![option(3, "title", "Song title", true)],
+        },
+        DiscordCommandSchema {
+            name: "random",
+            description: "Draws random diffs",
+            options: vec![
+                option(3, "filters", "Filters such as \"p:sp l:12\"", false),
+                option(4, "count", "Number of diffs", false),
             ],
-        });
+        },
+        DiscordCommandSchema {
+            name: "show",
+            description: "Shows a song by its ID",
+            options: vec![option(4, "id", "Song ID", true)],
+        },
+    ])
+}
+
+/// POST /slack/command
+pub async fn slack_command(
+    State(sd): State<SharedData>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AxumResult<Json<SlackMessage>> {
+    let catalog = sd.catalog.load_full();
+    let tokens = sd.tokens.load();
+    let Some(slack) = authorize_slack(&tokens, &headers, &body) else {
+        return Err(pass_token_error());
+    };
+    let form: SlackCommandForm = serde_urlencoded::from_bytes(&body).map_err(pass_form_error)?;
+    if !accepts_slack_team(slack, &form.team_id) {
+        return Err(pass_token_error());
     }
 
-    Ok(Json(Some(MattermostEnqueueResult {
-        username: "Cathedral".into(),
-        text: texts.join("\n"),
-        attachments,
-    })))
+    let engine = CommandEngine {
+        config: &sd.config,
+        catalog: &catalog,
+        userdata_pool: &sd.userdata_pool,
+    };
+    let context = CommandContext {
+        user_name: &form.user_name,
+        user_id: &form.user_id,
+        channel_id: &form.channel_id,
+        commands: &slack.commands,
+    };
+    let message = match engine.run(&context, &form.text).await {
+        Ok(result) => SlackRenderer {
+            emoji: &sd.config.bot.emoji,
+        }
+        .render(&result),
+        // Slack shows only a generic failure for non-200 responses
+        Err(err) => SlackMessage {
+            response_type: SlackResponseType::Ephemeral,
+            text: format!("{} {}: {err}", form.command, form.text),
+            blocks: vec![],
+        },
+    };
+
+    Ok(Json(message))
 }
 
-fn parse_extended_query(query: &str) -> Result<(Vec<FilterQuery>, usize), FilterQueryError> {
-    query
-        .trim()
-        .split_ascii_whitespace()
-        .try_fold(vec![], |mut fs, q| {
-            fs.push(q.parse()?);
-            Ok(fs)
-        })
-        .map(|q| (q, 1))
+/// POST /slack/events
+pub async fn slack_events(
+    State(sd): State<SharedData>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AxumResult<Json<Option<SlackChallengeResult>>> {
+    let tokens = sd.tokens.load();
+    let Some(slack) = authorize_slack(&tokens, &headers, &body) else {
+        return Err(pass_token_error());
+    };
+    let request: SlackEventRequest = serde_json::from_slice(&body).map_err(pass_json_error)?;
+    let (team_id, event) = match request {
+        SlackEventRequest::UrlVerification { challenge } => {
+            return Ok(Json(Some(SlackChallengeResult { challenge })));
+        }
+        SlackEventRequest::EventCallback { team_id, event } => (team_id, event),
+        SlackEventRequest::Unknown => return Ok(Json(None)),
+    };
+    if !accepts_slack_team(slack, &team_id) {
+        return Err(pass_token_error());
+    }
+
+    // Slack retries events unacknowledged in 3 seconds, which are already being replied to
+    if headers.contains_key("x-slack-retry-num") {
+        return Ok(Json(None));
+    }
+    if event.event_type != "app_mention" || event.bot_id.is_some() || slack.bot_token.is_none() {
+        return Ok(Json(None));
+    }
+
+    // replies are posted afterwards, so that the event is acknowledged in time
+    spawn(reply_slack_mention(sd.clone(), slack.clone(), event));
+    Ok(Json(None))
 }
 
-fn parse_compact_queries(text: &str) -> Vec<([FilterQuery; 3], usize)> {
-    let mut queries = vec![];
-    for query_text in text.trim().split_ascii_whitespace() {
-        if query_text.len() <= 3 {
-            continue;
+/// Verifies the signature of a Slack request.
+fn authorize_slack<'a>(
+    tokens: &'a TokenRegistry,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Option<&'a SlackConfig> {
+    let timestamp = headers
+        .get("x-slack-request-timestamp")
+        .and_then(|v| v.to_str().ok());
+    let signature = headers
+        .get("x-slack-signature")
+        .and_then(|v| v.to_str().ok());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let slack = tokens.authorize_slack(timestamp, signature, body, now);
+    if slack.is_none() {
+        warn!("Invalid Slack signature arrived");
+    }
+
+    slack
+}
+
+/// Checks that the request comes from the configured workspace, if any.
+fn accepts_slack_team(slack: &SlackConfig, team_id: &str) -> bool {
+    let accepted = match &slack.team_id {
+        Some(t) => t == team_id,
+        None => true,
+    };
+    if !accepted {
+        warn!("Slack request from unknown team {team_id} arrived");
+    }
+
+    accepted
+}
+
+/// Runs the query following the mention, and posts the result to the channel or the thread.
+async fn reply_slack_mention(sd: SharedData, slack: SlackConfig, event: SlackEvent) {
+    let catalog = sd.catalog.load_full();
+    let engine = CommandEngine {
+        config: &sd.config,
+        catalog: &catalog,
+        userdata_pool: &sd.userdata_pool,
+    };
+    // mentions carry no user name, so user lists are owned by the user ID
+    let context = CommandContext {
+        user_name: &event.user,
+        user_id: &event.user,
+        channel_id: &event.channel,
+        commands: &slack.commands,
+    };
+    let text = unescape_slack_text(strip_slack_mentions(&event.text));
+    let message = match engine.run(&context, &text).await {
+        Ok(result) => SlackRenderer {
+            emoji: &sd.config.bot.emoji,
         }
+        .render(&result),
+        Err(err) => SlackMessage {
+            response_type: SlackResponseType::InChannel,
+            text: format!("{text}: {err}"),
+            blocks: vec![],
+        },
+    };
+
+    let post = SlackPostMessage {
+        channel: event.channel,
+        thread_ts: event.thread_ts,
+        text: message.text,
+        blocks: message.blocks,
+    };
+    if let Err(err) = post_slack_message(&sd, &slack, &post).await {
+        warn!("Failed to reply to Slack mention: {err}");
+    }
+}
 
-        let (play_side, difficulty) = match &query_text[..3] {
-            "spb" => (
-                FilterQuery::PlaySide(PlaySide::Single),
-                FilterQuery::Difficulty(Difficulty::Beginner),
-            ),
-            "spn" => (
-                FilterQuery::PlaySide(PlaySide::Single),
-                FilterQuery::Difficulty(Difficulty::Normal),
-            ),
-            "sph" => (
-                FilterQuery::PlaySide(PlaySide::Single),
-                FilterQuery::Difficulty(Difficulty::Hyper),
-            ),
-            "spa" => (
-                FilterQuery::PlaySide(PlaySide::Single),
-                FilterQuery::Difficulty(Difficulty::Another),
-            ),
-            "spl" => (
-                FilterQuery::PlaySide(PlaySide::Single),
-                FilterQuery::Difficulty(Difficulty::Leggendaria),
-            ),
-            "dpn" => (
-                FilterQuery::PlaySide(PlaySide::Double),
-                FilterQuery::Difficulty(Difficulty::Normal),
-            ),
-            "dph" => (
-                FilterQuery::PlaySide(PlaySide::Double),
-                FilterQuery::Difficulty(Difficulty::Hyper),
-            ),
-            "dpa" => (
-                FilterQuery::PlaySide(PlaySide::Double),
-                FilterQuery::Difficulty(Difficulty::Another),
-            ),
-            "dpl" => (
-                FilterQuery::PlaySide(PlaySide::Double),
-                FilterQuery::Difficulty(Difficulty::Leggendaria),
-            ),
-            _ => continue,
-        };
-
-        let mut level_count = query_text[3..].split('*');
-        let Some(level) = level_count.next() else {
-            continue;
-        };
-        let Ok(level) = level.parse::<i64>() else {
-            continue;
-        };
-        let count = if let Some(count) = level_count.next() {
-            let Ok(value) = count.parse::<usize>() else {
-                continue;
-            };
-            value
-        } else {
-            1
-        };
-
-        queries.push(([play_side, difficulty, FilterQuery::Level(level)], count));
+/// Calls `chat.postMessage` of the Web API.
+async fn post_slack_message(
+    sd: &SharedData,
+    slack: &SlackConfig,
+    post: &SlackPostMessage,
+) -> Result<()> {
+    let bot_token = slack.bot_token.as_deref().unwrap_or_default();
+    let url = format!("{}/chat.postMessage", slack.api_url.trim_end_matches('/'));
+    let result: SlackApiResult = sd
+        .http_client
+        .post(url)
+        .bearer_auth(bot_token)
+        .json(post)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if !result.ok {
+        bail!("{}", result.error.unwrap_or_default());
     }
-    queries
+
+    Ok(())
+}
+
+/// Removes mentions leading the text, such as `<@U0123ABCD>` of the bot itself.
+fn strip_slack_mentions(text: &str) -> &str {
+    let mut text = text.trim_start();
+    while let Some((_, rest)) = text
+        .strip_prefix("<@")
+        .and_then(|mention| mention.split_once('>'))
+    {
+        text = rest.trim_start();
+    }
+    text
+}
+
+/// Restores characters Slack escapes in message text.
+fn unescape_slack_text(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
 }
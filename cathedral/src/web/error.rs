@@ -1,6 +1,9 @@
-use crate::{db::schema::FilterQueryError, web::schema::ErrorResult};
+use crate::{chat::schema::CommandError, db::schema::FilterQueryError, web::schema::ErrorResult};
 
+use anyhow::Error as AnyhowError;
 use axum::{http::StatusCode, response::ErrorResponse, Json};
+use serde_json::Error as JsonError;
+use serde_urlencoded::de::Error as FormError;
 use sqlx::Error as SqlxError;
 
 pub fn pass_sqlx_error(err: SqlxError) -> ErrorResponse {
@@ -23,6 +26,26 @@ pub fn pass_filter_query_error(err: FilterQueryError) -> ErrorResponse {
         .into()
 }
 
+pub fn pass_form_error(err: FormError) -> ErrorResponse {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResult {
+            reason: format!("form error: {}", err),
+        }),
+    )
+        .into()
+}
+
+pub fn pass_json_error(err: JsonError) -> ErrorResponse {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResult {
+            reason: format!("json error: {}", err),
+        }),
+    )
+        .into()
+}
+
 pub fn pass_not_found_error(subreason: &str) -> ErrorResponse {
     (
         StatusCode::NOT_FOUND,
@@ -33,6 +56,16 @@ pub fn pass_not_found_error(subreason: &str) -> ErrorResponse {
         .into()
 }
 
+pub fn pass_reload_error(err: AnyhowError) -> ErrorResponse {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResult {
+            reason: format!("reload error: {}", err),
+        }),
+    )
+        .into()
+}
+
 pub fn pass_token_error() -> ErrorResponse {
     (
         StatusCode::UNAUTHORIZED,
@@ -42,3 +75,28 @@ pub fn pass_token_error() -> ErrorResponse {
     )
         .into()
 }
+
+pub fn pass_admin_error() -> ErrorResponse {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(ErrorResult {
+            reason: "unauthorized admin token".to_string(),
+        }),
+    )
+        .into()
+}
+
+pub fn pass_command_error(err: CommandError) -> ErrorResponse {
+    let status = match err {
+        CommandError::Sqlx(_) | CommandError::FilterQuery(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        CommandError::NotFound(_) => StatusCode::NOT_FOUND,
+        CommandError::Forbidden(_) => StatusCode::FORBIDDEN,
+    };
+    (
+        status,
+        Json(ErrorResult {
+            reason: err.to_string(),
+        }),
+    )
+        .into()
+}
@@ -0,0 +1,110 @@
+use crate::{
+    db::schema::{
+        Availability, Diff, DiffList, Difficulty, Lamp, ListScope, NoteType, Platform, PlaySide,
+        Score, ScoreUpdate, ScratchType, Song, SongSort, SortOrder, StatsCount, Tier, Version,
+    },
+    web::{action::*, schema::*},
+};
+
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        OpenApi as OpenApiDocument,
+    },
+    Modify, OpenApi,
+};
+
+/// OpenAPI document of the JSON endpoints. Chat webhooks follow each platform's format,
+/// and `/songs/live` is a WebSocket which OpenAPI cannot describe.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Cathedral", license(name = "Apache-2.0")),
+    paths(
+        songs_search,
+        songs_suggest,
+        songs_show,
+        versions_index,
+        versions_songs,
+        stats,
+        users_scores,
+        users_scores_update,
+        lists_index,
+        lists_show,
+        lists_create,
+        lists_delete,
+        lists_entries_add,
+        lists_entries_remove,
+        admin_reload
+    ),
+    components(schemas(
+        ErrorResult,
+        SearchSort,
+        SongsSearchResponse,
+        SongsSuggestResponse,
+        SongsShowResponse,
+        VersionsSongsResponse,
+        StatsResponse,
+        ListsShowResponse,
+        ListEntryQuery,
+        AdminReloadResult,
+        Song,
+        Diff,
+        Tier,
+        Availability,
+        Version,
+        StatsCount,
+        Score,
+        ScoreUpdate,
+        DiffList,
+        SongSort,
+        SortOrder,
+        PlaySide,
+        Difficulty,
+        NoteType,
+        ScratchType,
+        Platform,
+        Lamp,
+        ListScope
+    )),
+    modifiers(&AdminTokenScheme),
+    tags(
+        (name = "songs", description = "Song search and details"),
+        (name = "versions", description = "Songs by version"),
+        (name = "stats", description = "Diff counts by filters"),
+        (name = "users", description = "Scores of users"),
+        (name = "lists", description = "Diff lists of users and channels"),
+        (name = "admin", description = "Server maintenance"),
+    )
+)]
+pub struct ApiDoc;
+
+/// `Authorization: Bearer <admin token>` required by updating endpoints.
+struct AdminTokenScheme;
+
+impl Modify for AdminTokenScheme {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let Some(components) = openapi.components.as_mut() else {
+            return;
+        };
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiDoc;
+
+    use utoipa::OpenApi;
+
+    /// Fails when the schema types or handlers change. Review with `cargo insta review`.
+    #[test]
+    fn openapi_matches_snapshot() {
+        let document = ApiDoc::openapi()
+            .to_pretty_json()
+            .expect("document should be serialized");
+        insta::assert_snapshot!(document);
+    }
+}
@@ -1,19 +1,45 @@
-use crate::db::schema::{Diff, Song};
+use crate::db::schema::{
+    Availability, Diff, DiffList, Difficulty, PlaySide, Song, SongSort, SortOrder, StatsCount,
+    Tier, Version,
+};
 
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct ErrorResult {
     pub reason: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SongsSearchQuery {
     pub q: String,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub sort: SearchSort,
+    #[serde(default)]
+    pub order: SortOrder,
+
+    /// Excludes songs whose score exceeds this.
+    pub max_score: Option<isize>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSort {
+    /// Most relevant first in ascending order.
+    #[default]
+    Score,
+    Title,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct SongsSearchResponse {
+    /// Edit distance from the query. Lower is more relevant.
+    pub score: isize,
     pub version_abbrev: String,
     pub id: i64,
     pub genre: String,
@@ -21,35 +47,184 @@ pub struct SongsSearchResponse {
     pub artist: String,
 }
 
+/// Query sent to `/songs/live`. `id` is echoed back in the response.
 #[derive(Debug, Clone, Deserialize)]
+pub struct LiveSearchRequest {
+    pub id: u64,
+    #[serde(flatten)]
+    pub query: SongsSearchQuery,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LiveSearchResponse {
+    Results {
+        id: u64,
+        results: Vec<SongsSearchResponse>,
+    },
+    Error {
+        id: Option<u64>,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SongsSuggestQuery {
+    pub prefix: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SongsSuggestResponse {
+    pub id: i64,
+    pub title: String,
+    /// Title or alias which matched the prefix.
+    pub matched: String,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SongsShowQuery {
     pub id: i64,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct SongsShowResponse {
     pub song: Song,
     pub diffs: Vec<Diff>,
+    pub tiers: Vec<Tier>,
+    pub availabilities: Vec<Availability>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VersionsSongsQuery {
+    #[serde(default)]
+    pub sort: SongSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VersionsSongsResponse {
+    pub version: Version,
+    /// Number of all songs in the version, regardless of `limit` and `offset`.
+    pub total: i64,
+    pub songs: Vec<Song>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    /// Filters separated by spaces, same as `?` queries.
+    #[serde(default)]
+    pub q: String,
+
+    /// Grouping key, like `level` or `bpm:20`.
+    pub group: String,
+
+    /// User whom score-dependent filters refer to.
+    #[serde(default)]
+    pub user_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StatsResponse {
+    pub total: i64,
+    pub counts: Vec<StatsCount>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UsersScoresQuery {
+    pub song_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ListsShowResponse {
+    pub list: DiffList,
+    pub diffs: Vec<Diff>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListEntryQuery {
+    pub song_id: i64,
+    pub play_side: PlaySide,
+    pub difficulty: Difficulty,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AdminReloadResult {
+    pub songs_count: usize,
+    pub webhooks_count: usize,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct MattermostEnqueueForm {
     pub token: String,
     pub text: String,
+
+    #[serde(default)]
+    pub team_id: String,
+    #[serde(default)]
+    pub channel_id: String,
+    #[serde(default)]
+    pub user_id: String,
+    #[serde(default)]
+    pub user_name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MattermostCommandForm {
+    pub token: String,
+    pub command: String,
+    pub text: String,
+
+    #[serde(default)]
+    pub team_id: String,
+    #[serde(default)]
+    pub channel_id: String,
+    #[serde(default)]
+    pub user_id: String,
+    #[serde(default)]
+    pub user_name: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct MattermostEnqueueResult {
+    /// Only for slash commands. Outgoing webhooks always post to the channel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_type: Option<MattermostResponseType>,
     pub username: String,
     pub text: String,
     pub attachments: Vec<AttachmentSongInfo>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MattermostResponseType {
+    /// Visible only to the sender.
+    Ephemeral,
+
+    /// Posted to the channel.
+    InChannel,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct AttachmentSongInfo {
+    /// Shown in notifications and clients without formatting.
+    pub fallback: String,
     pub title: String,
     pub footer: String,
     pub fields: Vec<AttachmentSongField>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<AttachmentAction>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -58,3 +233,342 @@ pub struct AttachmentSongField {
     pub title: String,
     pub value: String,
 }
+
+/// Button which Mattermost posts back to `integration.url` with the context.
+#[derive(Debug, Clone, Serialize)]
+pub struct AttachmentAction {
+    /// Alphanumeric only.
+    pub id: String,
+    pub name: String,
+    pub integration: ActionIntegration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionIntegration {
+    pub url: String,
+    pub context: MattermostActionContext,
+}
+
+/// State of the message carried by buttons. Mattermost doesn't send it to clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MattermostActionContext {
+    /// Webhook token which the message was posted with.
+    pub token: String,
+    pub action: MattermostAction,
+
+    /// Index in `slots` of the diff to act on.
+    pub slot: usize,
+    pub slots: Vec<ActionSlot>,
+    pub song_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MattermostAction {
+    /// Draws another diff in place of the slot.
+    Reroll,
+
+    /// Shows the song of the slot.
+    Show,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionSlot {
+    pub song_id: i64,
+    pub play_side: PlaySide,
+    pub difficulty: Difficulty,
+    pub redraw: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MattermostActionRequest {
+    #[serde(default)]
+    pub team_id: String,
+    #[serde(default)]
+    pub channel_id: String,
+    #[serde(default)]
+    pub user_id: String,
+    #[serde(default)]
+    pub user_name: String,
+    pub context: MattermostActionContext,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MattermostActionResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update: Option<MattermostPostUpdate>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ephemeral_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MattermostPostUpdate {
+    pub message: String,
+    pub props: MattermostPostProps,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MattermostPostProps {
+    pub attachments: Vec<AttachmentSongInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlackCommandForm {
+    pub command: String,
+    pub text: String,
+
+    #[serde(default)]
+    pub team_id: String,
+    #[serde(default)]
+    pub channel_id: String,
+    #[serde(default)]
+    pub user_id: String,
+    #[serde(default)]
+    pub user_name: String,
+}
+
+/// Message in Block Kit. `text` is shown in notifications.
+#[derive(Debug, Clone, Serialize)]
+pub struct SlackMessage {
+    pub response_type: SlackResponseType,
+    pub text: String,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<SlackBlock>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlackResponseType {
+    InChannel,
+    Ephemeral,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SlackBlock {
+    Section {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<SlackText>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        fields: Vec<SlackText>,
+    },
+    Context {
+        elements: Vec<SlackText>,
+    },
+    Divider,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "mrkdwn")]
+pub struct SlackText {
+    pub text: String,
+}
+
+/// Request of the Events API. Subscriptions other than events are ignored.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SlackEventRequest {
+    UrlVerification {
+        challenge: String,
+    },
+    EventCallback {
+        #[serde(default)]
+        team_id: String,
+        event: SlackEvent,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlackEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub channel: String,
+
+    /// Set if the event happened in a thread.
+    pub thread_ts: Option<String>,
+
+    /// Set if a bot sent the message.
+    pub bot_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlackChallengeResult {
+    pub challenge: String,
+}
+
+/// Arguments of `chat.postMessage`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SlackPostMessage {
+    pub channel: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_ts: Option<String>,
+    pub text: String,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<SlackBlock>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlackApiResult {
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// Interaction sent from Discord. Only fields used by the bot are read.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordInteraction {
+    #[serde(rename = "type")]
+    pub kind: DiscordInteractionType,
+    pub data: Option<DiscordCommandData>,
+    pub channel_id: Option<String>,
+
+    /// Sender in guilds.
+    pub member: Option<DiscordMember>,
+
+    /// Sender in direct messages.
+    pub user: Option<DiscordUser>,
+}
+
+impl DiscordInteraction {
+    pub fn sender(&self) -> Option<&DiscordUser> {
+        self.member.as_ref().map(|m| &m.user).or(self.user.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(from = "u8")]
+pub enum DiscordInteractionType {
+    Ping,
+    ApplicationCommand,
+    Other(u8),
+}
+
+impl From<u8> for DiscordInteractionType {
+    fn from(value: u8) -> DiscordInteractionType {
+        match value {
+            1 => DiscordInteractionType::Ping,
+            2 => DiscordInteractionType::ApplicationCommand,
+            other => DiscordInteractionType::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordCommandData {
+    pub name: String,
+    #[serde(default)]
+    pub options: Vec<DiscordOptionValue>,
+}
+
+impl DiscordCommandData {
+    pub fn option(&self, name: &str) -> Option<&serde_json::Value> {
+        self.options
+            .iter()
+            .find(|o| o.name == name)
+            .map(|o| &o.value)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordOptionValue {
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordMember {
+    pub user: DiscordUser,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordUser {
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscordInteractionResponse {
+    #[serde(rename = "type")]
+    pub kind: DiscordResponseType,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<DiscordMessage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(into = "u8")]
+pub enum DiscordResponseType {
+    Pong,
+    ChannelMessageWithSource,
+}
+
+impl From<DiscordResponseType> for u8 {
+    fn from(value: DiscordResponseType) -> u8 {
+        match value {
+            DiscordResponseType::Pong => 1,
+            DiscordResponseType::ChannelMessageWithSource => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscordMessage {
+    pub content: String,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub embeds: Vec<DiscordEmbed>,
+
+    /// `DISCORD_EPHEMERAL` to show only to the sender.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<u64>,
+}
+
+pub const DISCORD_EPHEMERAL: u64 = 1 << 6;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscordEmbed {
+    pub title: String,
+    pub fields: Vec<DiscordEmbedField>,
+    pub footer: DiscordEmbedFooter,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscordEmbedField {
+    pub name: String,
+    pub value: String,
+    pub inline: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscordEmbedFooter {
+    pub text: String,
+}
+
+/// Application command definition to register with the Discord API.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscordCommandSchema {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub options: Vec<DiscordCommandOption>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscordCommandOption {
+    /// 3 for strings, 4 for integers.
+    #[serde(rename = "type")]
+    pub kind: u8,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub required: bool,
+}
@@ -0,0 +1,46 @@
+use crate::data::{Alias, AliasKind};
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawAlias {
+    title: String,
+    kind: String,
+    alias: String,
+}
+
+/// Reads alias CSV.
+pub fn read_alias_csv(path: &Path) -> Result<Vec<Alias>> {
+    let raw_aliases: Vec<RawAlias> = csv::Reader::from_path(path)?
+        .deserialize()
+        .collect::<Result<_, _>>()?;
+
+    raw_aliases
+        .into_iter()
+        .enumerate()
+        .map(|(i, raw)| {
+            convert_alias(raw).with_context(|| format!("invalid alias at row {}", i + 1))
+        })
+        .collect()
+}
+
+fn convert_alias(raw: RawAlias) -> Result<Alias> {
+    let kind = match raw.kind.trim().to_ascii_lowercase().as_str() {
+        "reading" => AliasKind::Reading,
+        "alias" => AliasKind::Alias,
+        other => bail!("unknown alias kind: {other}"),
+    };
+    let alias = raw.alias.trim().to_string();
+    if alias.is_empty() {
+        bail!("empty alias");
+    }
+
+    Ok(Alias {
+        title: raw.title.trim().to_string(),
+        kind,
+        alias,
+    })
+}
@@ -0,0 +1,157 @@
+use crate::data::{AvailabilityEvent, AvailabilityEventKind, AvailabilityPeriod, Platform};
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawAvailabilityEvent {
+    title: String,
+    platform: String,
+    event: String,
+    version: Option<usize>,
+}
+
+/// Reads availability events CSV. Events of each song must be in chronological order.
+pub fn read_availability_events(path: &Path) -> Result<Vec<AvailabilityEvent>> {
+    let raw_events: Vec<RawAvailabilityEvent> = csv::Reader::from_path(path)?
+        .deserialize()
+        .collect::<Result<_, _>>()?;
+
+    raw_events
+        .into_iter()
+        .enumerate()
+        .map(|(i, raw)| {
+            convert_event(raw).with_context(|| format!("invalid event at row {}", i + 1))
+        })
+        .collect()
+}
+
+/// Applies events of one song on one platform to its periods.
+pub fn fold_periods(
+    initial: Vec<AvailabilityPeriod>,
+    events: &[&AvailabilityEvent],
+) -> Result<Vec<AvailabilityPeriod>> {
+    let mut periods = initial;
+    for event in events {
+        let open = periods.last().map(|p| !p.deleted).unwrap_or(false);
+        match (event.kind, open) {
+            (AvailabilityEventKind::Added | AvailabilityEventKind::Revived, false) => {
+                periods.push(AvailabilityPeriod {
+                    added_version_number: event.version_number,
+                    deleted: false,
+                    deleted_version_number: None,
+                });
+            }
+            (AvailabilityEventKind::Deleted, true) => {
+                if let Some(last) = periods.last_mut() {
+                    last.deleted = true;
+                    last.deleted_version_number = event.version_number;
+                }
+            }
+            (AvailabilityEventKind::Added | AvailabilityEventKind::Revived, true) => {
+                bail!("{} is already playable", event.title);
+            }
+            (AvailabilityEventKind::Deleted, false) => {
+                bail!("{} is not playable", event.title);
+            }
+        }
+    }
+
+    Ok(periods)
+}
+
+fn convert_event(raw: RawAvailabilityEvent) -> Result<AvailabilityEvent> {
+    let platform = match raw.platform.trim().to_ascii_lowercase().as_str() {
+        "ac" | "arcade" => Platform::Arcade,
+        "inf" | "infinitas" => Platform::Infinitas,
+        "cs" | "consumer" => Platform::Consumer,
+        other => bail!("unknown platform: {other}"),
+    };
+    let kind = match raw.event.trim().to_ascii_lowercase().as_str() {
+        "added" => AvailabilityEventKind::Added,
+        "deleted" => AvailabilityEventKind::Deleted,
+        "revived" => AvailabilityEventKind::Revived,
+        other => bail!("unknown event: {other}"),
+    };
+    if platform == Platform::Arcade && raw.version.is_none() {
+        bail!("arcade event requires version");
+    }
+
+    Ok(AvailabilityEvent {
+        title: raw.title.trim().to_string(),
+        platform,
+        kind,
+        version_number: raw.version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fold_periods;
+    use crate::data::{AvailabilityEvent, AvailabilityEventKind, AvailabilityPeriod, Platform};
+
+    fn event(kind: AvailabilityEventKind, version_number: usize) -> AvailabilityEvent {
+        AvailabilityEvent {
+            title: "Title".into(),
+            platform: Platform::Arcade,
+            kind,
+            version_number: Some(version_number),
+        }
+    }
+
+    #[test]
+    fn folds_added_deleted_revived() {
+        let events = [
+            event(AvailabilityEventKind::Added, 20),
+            event(AvailabilityEventKind::Deleted, 25),
+            event(AvailabilityEventKind::Revived, 29),
+        ];
+        let periods = fold_periods(vec![], &events.iter().collect::<Vec<_>>())
+            .expect("events should be folded");
+
+        assert_eq!(
+            periods,
+            vec![
+                AvailabilityPeriod {
+                    added_version_number: Some(20),
+                    deleted: true,
+                    deleted_version_number: Some(25),
+                },
+                AvailabilityPeriod {
+                    added_version_number: Some(29),
+                    deleted: false,
+                    deleted_version_number: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_delete_while_not_playable() {
+        let deleted = event(AvailabilityEventKind::Deleted, 25);
+        assert!(fold_periods(vec![], &[&deleted]).is_err());
+
+        let closed = AvailabilityPeriod {
+            added_version_number: Some(20),
+            deleted: true,
+            deleted_version_number: Some(22),
+        };
+        assert!(fold_periods(vec![closed], &[&deleted]).is_err());
+    }
+
+    #[test]
+    fn rejects_double_add() {
+        let added = event(AvailabilityEventKind::Added, 20);
+        let revived = event(AvailabilityEventKind::Revived, 21);
+        assert!(fold_periods(vec![], &[&added, &added]).is_err());
+
+        let open = AvailabilityPeriod {
+            added_version_number: Some(20),
+            deleted: false,
+            deleted_version_number: None,
+        };
+        assert!(fold_periods(vec![open], &[&revived]).is_err());
+    }
+}
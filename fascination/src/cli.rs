@@ -1,13 +1,99 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-/// Parses BEMANIwiki-style song table.
+/// Imports song tables and related data into the catalog.
+///
+/// Without subcommand, `fascination <SQLITE_FILE> <TABLE_HTML>` imports songs like `songs`.
 #[derive(Debug, Clone, Parser)]
+#[clap(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
 pub struct Arguments {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    #[clap(flatten)]
+    pub songs: Option<SongsArguments>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    Songs(SongsArguments),
+    Tiers(TiersArguments),
+    Scores(ScoresArguments),
+    Availability(AvailabilityArguments),
+    Aliases(AliasesArguments),
+}
+
+/// Parses BEMANIwiki-style song table.
+#[derive(Debug, Clone, Parser)]
+pub struct SongsArguments {
     pub sqlite_file: PathBuf,
     pub table_html: PathBuf,
 
     #[clap(short = 'v', long)]
     pub default_version: Option<String>,
 }
+
+/// Imports community difficulty tiers from CSV or JSON list.
+#[derive(Debug, Clone, Parser)]
+pub struct TiersArguments {
+    pub sqlite_file: PathBuf,
+
+    /// CSV (title,play_side,difficulty,tier) or JSON array of the same fields.
+    pub tier_list: PathBuf,
+
+    /// Short name used in filters, like `hc`.
+    #[clap(short, long)]
+    pub abbrev: String,
+
+    /// Display name, like `SP12 hard clear`.
+    #[clap(short, long)]
+    pub name: String,
+}
+
+/// Imports score CSV exported from the official site into the user data store.
+#[derive(Debug, Clone, Parser)]
+pub struct ScoresArguments {
+    pub sqlite_file: PathBuf,
+    pub userdata_file: PathBuf,
+    pub score_csv: PathBuf,
+
+    /// User name which the scores belong to.
+    #[clap(short, long)]
+    pub user: String,
+
+    /// Play side of the CSV (sp or dp).
+    #[clap(short, long)]
+    pub play_side: String,
+
+    /// Maximum title distance accepted as the same song.
+    #[clap(long, default_value = "20")]
+    pub max_distance: isize,
+}
+
+/// Imports deletion and revival events of songs on each platform.
+#[derive(Debug, Clone, Parser)]
+pub struct AvailabilityArguments {
+    pub sqlite_file: PathBuf,
+
+    /// CSV (title,platform,event,version). Platform is ac, inf or cs,
+    /// event is added, deleted or revived, and version is the arcade version number.
+    pub event_csv: PathBuf,
+
+    /// Maximum title distance accepted as the same song.
+    #[clap(long, default_value = "20")]
+    pub max_distance: isize,
+}
+
+/// Imports readings and nicknames of songs used for suggestions.
+#[derive(Debug, Clone, Parser)]
+pub struct AliasesArguments {
+    pub sqlite_file: PathBuf,
+
+    /// CSV (title,kind,alias). Kind is reading or alias.
+    pub alias_csv: PathBuf,
+
+    /// Maximum title distance accepted as the same song.
+    #[clap(long, default_value = "20")]
+    pub max_distance: isize,
+}
@@ -26,11 +26,23 @@ pub enum PlaySide {
     Double,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bpm {
+    Fixed(usize),
+    Range(usize, usize),
+    Indeterminate,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Diff {
     pub play_side: PlaySide,
     pub difficulty: Difficulty,
     pub level: usize,
+    pub notes: Option<usize>,
+    /// Chart-specific tempo if it differs from the song.
+    pub bpm: Option<Bpm>,
+    /// Version number in which the chart was added, if later than the song.
+    pub added_version_number: Option<usize>,
     pub note_type: Option<NoteType>,
     pub scratch_type: Option<ScratchType>,
 }
@@ -40,8 +52,7 @@ pub struct Song {
     pub genre: String,
     pub title: String,
     pub artist: String,
-    pub min_bpm: Option<usize>,
-    pub max_bpm: usize,
+    pub bpm: Bpm,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -56,3 +67,77 @@ pub enum Subheader {
     Version(Version),
     Event(String),
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tier {
+    pub title: String,
+    pub play_side: PlaySide,
+    pub difficulty: Difficulty,
+    pub tier: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lamp {
+    NoPlay,
+    Failed,
+    AssistClear,
+    EasyClear,
+    Clear,
+    HardClear,
+    ExHardClear,
+    FullCombo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Score {
+    pub title: String,
+    pub difficulty: Difficulty,
+    pub lamp: Lamp,
+    pub ex_score: Option<usize>,
+    pub miss_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    Arcade,
+    Infinitas,
+    Consumer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvailabilityEventKind {
+    Added,
+    Deleted,
+    Revived,
+}
+
+/// Change of song availability on the platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvailabilityEvent {
+    pub title: String,
+    pub platform: Platform,
+    pub kind: AvailabilityEventKind,
+    /// Arcade version number, if the platform follows it.
+    pub version_number: Option<usize>,
+}
+
+/// Period in which the song is playable on the platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AvailabilityPeriod {
+    pub added_version_number: Option<usize>,
+    pub deleted: bool,
+    pub deleted_version_number: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasKind {
+    Reading,
+    Alias,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    pub title: String,
+    pub kind: AliasKind,
+    pub alias: String,
+}
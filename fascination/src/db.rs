@@ -1,9 +1,19 @@
-use crate::data::{Diff, Difficulty, NoteType, PlaySide, ScratchType, Song, Version};
+use crate::data::{
+    Alias, AliasKind, AvailabilityPeriod, Bpm, Diff, Difficulty, Lamp, NoteType, Platform,
+    PlaySide, Score, ScratchType, Song, Tier, Version,
+};
 
 use std::path::Path;
 
 use anyhow::Result;
-use sqlx::{query, Row, SqlitePool};
+use sqlx::{query, Row, SqliteConnection, SqlitePool};
+
+/// Schema scripts of user data store, same as cathedral applies.
+const USERDATA_MIGRATIONS: &[&str] = &[
+    include_str!("../../sql/userdata/01-draw_history.sql"),
+    include_str!("../../sql/userdata/02-scores.sql"),
+    include_str!("../../sql/userdata/03-lists.sql"),
+];
 
 pub async fn open_sqlite_file(path: &Path) -> Result<SqlitePool> {
     let conn = SqlitePool::connect(&format!(
@@ -14,6 +24,33 @@ pub async fn open_sqlite_file(path: &Path) -> Result<SqlitePool> {
     Ok(conn)
 }
 
+/// Opens user data store of cathedral, creating and migrating it if needed.
+pub async fn open_userdata_file(path: &Path) -> Result<SqlitePool> {
+    let conn = SqlitePool::connect(&format!(
+        "sqlite://{}?mode=rwc",
+        path.to_str().expect("invalid filename")
+    ))
+    .await?;
+
+    let mut tx = conn.begin().await?;
+    let (applied,): (i64,) = sqlx::query_as("PRAGMA user_version;")
+        .fetch_one(&mut *tx)
+        .await?;
+    for (index, script) in USERDATA_MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(applied as usize)
+    {
+        query(script).execute(&mut *tx).await?;
+        query(&format!("PRAGMA user_version = {};", index + 1))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(conn)
+}
+
 pub async fn insert_version(pool: &SqlitePool, version: &Version) -> Result<i64> {
     let returned_id = query(
         r#"
@@ -37,10 +74,11 @@ pub async fn insert_song(
     event: Option<&str>,
     song: &Song,
 ) -> Result<i64> {
+    let (min_bpm, max_bpm) = bpm_columns(song.bpm);
     let returned_id = query(
         r#"
-        INSERT INTO "songs" ("version_id", "genre", "title", "artist", "min_bpm", "max_bpm", "unlock_info")
-        VALUES (?, ?, ?, ?, ?, ?, ?)
+        INSERT INTO "songs" ("version_id", "genre", "title", "artist", "min_bpm", "max_bpm", "bpm_indeterminate", "unlock_info")
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
         RETURNING "id";
         "#,
     )
@@ -48,8 +86,9 @@ pub async fn insert_song(
     .bind(&song.genre)
     .bind(&song.title)
     .bind(&song.artist)
-    .bind(song.min_bpm.map(|x| x as i64))
-    .bind(song.max_bpm as i64)
+    .bind(min_bpm)
+    .bind(max_bpm)
+    .bind(song.bpm == Bpm::Indeterminate)
     .bind(event)
     .fetch_one(pool)
     .await?;
@@ -57,19 +96,32 @@ pub async fn insert_song(
     Ok(returned_id.get("id"))
 }
 
+/// Records that the song is playable on ARCADE since its debut version.
+pub async fn insert_debut_availability(
+    pool: &SqlitePool,
+    song_id: i64,
+    version_id: i64,
+) -> Result<()> {
+    query(
+        r#"
+        INSERT INTO "availabilities" ("song_id", "platform", "added_version_number")
+        SELECT ?, 'ARCADE', "number"
+        FROM "versions"
+        WHERE "id" = ?;
+        "#,
+    )
+    .bind(song_id)
+    .bind(version_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn insert_diffs(pool: &SqlitePool, song_id: i64, diffs: &[Diff]) -> Result<()> {
     for diff in diffs {
-        let play_side = match diff.play_side {
-            PlaySide::Single => "SP",
-            PlaySide::Double => "DP",
-        };
-        let difficulty = match diff.difficulty {
-            Difficulty::Beginner => "BEGINNER",
-            Difficulty::Normal => "NORMAL",
-            Difficulty::Hyper => "HYPER",
-            Difficulty::Another => "ANOTHER",
-            Difficulty::Leggendaria => "LEGGENDARIA",
-        };
+        let play_side = play_side_str(diff.play_side);
+        let difficulty = difficulty_str(diff.difficulty);
         let cn_type = diff.note_type.map(|nt| match nt {
             NoteType::Charge => "CN",
             NoteType::HellCharge => "HCN",
@@ -79,17 +131,22 @@ pub async fn insert_diffs(pool: &SqlitePool, song_id: i64, diffs: &[Diff]) -> Re
             ScratchType::HellBack => "HBSS",
             ScratchType::Multi => "MSS",
         });
+        let (min_bpm, max_bpm) = diff.bpm.map(bpm_columns).unwrap_or_default();
 
         sqlx::query(
             r#"
-            INSERT INTO "diffs" ("song_id", "play_side", "difficulty", "level", "cn_type", "bss_type")
-            VALUES (?, ?, ?, ?, ?, ?);
+            INSERT INTO "diffs" ("song_id", "play_side", "difficulty", "level", "notes", "min_bpm", "max_bpm", "bpm_indeterminate", "cn_type", "bss_type")
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
             "#,
         )
         .bind(song_id)
         .bind(play_side)
         .bind(difficulty)
         .bind(diff.level as i64)
+        .bind(diff.notes.map(|x| x as i64))
+        .bind(min_bpm)
+        .bind(max_bpm)
+        .bind(diff.bpm == Some(Bpm::Indeterminate))
         .bind(cn_type)
         .bind(bss_type)
         .execute(pool)
@@ -98,3 +155,268 @@ pub async fn insert_diffs(pool: &SqlitePool, song_id: i64, diffs: &[Diff]) -> Re
 
     Ok(())
 }
+
+/// Sets the version in which the chart was added. Returns false if the version is not found.
+pub async fn update_diff_added_version(
+    pool: &SqlitePool,
+    song_id: i64,
+    diff: &Diff,
+    version_number: usize,
+) -> Result<bool> {
+    let result = query(
+        r#"
+        UPDATE "diffs"
+        SET "added_version_id" = (SELECT "id" FROM "versions" WHERE "number" = ? ORDER BY "id" LIMIT 1)
+        WHERE "song_id" = ? AND "play_side" = ? AND "difficulty" = ?
+            AND EXISTS (SELECT 1 FROM "versions" WHERE "number" = ?);
+        "#,
+    )
+    .bind(version_number as i64)
+    .bind(song_id)
+    .bind(play_side_str(diff.play_side))
+    .bind(difficulty_str(diff.difficulty))
+    .bind(version_number as i64)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn fetch_title_pairs(pool: &SqlitePool) -> Result<Vec<(i64, String)>> {
+    let rows = sqlx::query_as(
+        r#"
+        SELECT "id", "title"
+        FROM "songs"
+        ORDER BY "id";
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn diff_exists(
+    pool: &SqlitePool,
+    song_id: i64,
+    play_side: PlaySide,
+    difficulty: Difficulty,
+) -> Result<bool> {
+    let row = query(
+        r#"
+        SELECT 1
+        FROM "diffs"
+        WHERE "song_id" = ? AND "play_side" = ? AND "difficulty" = ?;
+        "#,
+    )
+    .bind(song_id)
+    .bind(play_side_str(play_side))
+    .bind(difficulty_str(difficulty))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.is_some())
+}
+
+/// Inserts or renames tier table, and removes its existing tiers.
+pub async fn reset_tier_table(
+    conn: &mut SqliteConnection,
+    abbrev: &str,
+    name: &str,
+) -> Result<i64> {
+    let returned_id = query(
+        r#"
+        INSERT INTO "tier_tables" ("name", "abbrev")
+        VALUES (?, ?)
+        ON CONFLICT ("abbrev") DO UPDATE SET "name" = "excluded"."name"
+        RETURNING "id";
+        "#,
+    )
+    .bind(name)
+    .bind(abbrev)
+    .fetch_one(&mut *conn)
+    .await?;
+    let tier_table_id: i64 = returned_id.get("id");
+
+    query(r#"DELETE FROM "tiers" WHERE "tier_table_id" = ?;"#)
+        .bind(tier_table_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(tier_table_id)
+}
+
+pub async fn insert_tier(
+    conn: &mut SqliteConnection,
+    tier_table_id: i64,
+    song_id: i64,
+    tier: &Tier,
+    tier_order: usize,
+) -> Result<()> {
+    query(
+        r#"
+        INSERT INTO "tiers" ("tier_table_id", "song_id", "play_side", "difficulty", "tier", "tier_order")
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT DO UPDATE SET "tier" = "excluded"."tier", "tier_order" = "excluded"."tier_order";
+        "#,
+    )
+    .bind(tier_table_id)
+    .bind(song_id)
+    .bind(play_side_str(tier.play_side))
+    .bind(difficulty_str(tier.difficulty))
+    .bind(&tier.tier)
+    .bind(tier_order as i64)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fetch_debut_version_number(pool: &SqlitePool, song_id: i64) -> Result<usize> {
+    let row = query(
+        r#"
+        SELECT "versions"."number"
+        FROM "songs"
+        INNER JOIN "versions" ON "songs"."version_id" = "versions"."id"
+        WHERE "songs"."id" = ?;
+        "#,
+    )
+    .bind(song_id)
+    .fetch_one(pool)
+    .await?;
+    let number: i64 = row.get("number");
+
+    Ok(number as usize)
+}
+
+/// Replaces all periods of the song on the platform.
+pub async fn replace_availabilities(
+    pool: &SqlitePool,
+    song_id: i64,
+    platform: Platform,
+    periods: &[AvailabilityPeriod],
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    query(r#"DELETE FROM "availabilities" WHERE "song_id" = ? AND "platform" = ?;"#)
+        .bind(song_id)
+        .bind(platform_str(platform))
+        .execute(&mut *tx)
+        .await?;
+
+    for period in periods {
+        query(
+            r#"
+            INSERT INTO "availabilities" ("song_id", "platform", "added_version_number", "deleted", "deleted_version_number")
+            VALUES (?, ?, ?, ?, ?);
+            "#,
+        )
+        .bind(song_id)
+        .bind(platform_str(platform))
+        .bind(period.added_version_number.map(|x| x as i64))
+        .bind(period.deleted)
+        .bind(period.deleted_version_number.map(|x| x as i64))
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn insert_alias(pool: &SqlitePool, song_id: i64, alias: &Alias) -> Result<()> {
+    let kind = match alias.kind {
+        AliasKind::Reading => "READING",
+        AliasKind::Alias => "ALIAS",
+    };
+
+    query(
+        r#"
+        INSERT INTO "song_aliases" ("song_id", "kind", "alias")
+        VALUES (?, ?, ?)
+        ON CONFLICT DO NOTHING;
+        "#,
+    )
+    .bind(song_id)
+    .bind(kind)
+    .bind(&alias.alias)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn upsert_score(
+    pool: &SqlitePool,
+    user_name: &str,
+    song_id: i64,
+    play_side: PlaySide,
+    score: &Score,
+) -> Result<()> {
+    let lamp = match score.lamp {
+        Lamp::NoPlay => 0,
+        Lamp::Failed => 1,
+        Lamp::AssistClear => 2,
+        Lamp::EasyClear => 3,
+        Lamp::Clear => 4,
+        Lamp::HardClear => 5,
+        Lamp::ExHardClear => 6,
+        Lamp::FullCombo => 7,
+    };
+
+    query(
+        r#"
+        INSERT INTO "scores" ("user_name", "song_id", "play_side", "difficulty", "lamp", "ex_score", "miss_count", "updated_at")
+        VALUES (?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))
+        ON CONFLICT DO UPDATE SET
+            "lamp" = "excluded"."lamp",
+            "ex_score" = "excluded"."ex_score",
+            "miss_count" = "excluded"."miss_count",
+            "updated_at" = "excluded"."updated_at";
+        "#,
+    )
+    .bind(user_name)
+    .bind(song_id)
+    .bind(play_side_str(play_side))
+    .bind(difficulty_str(score.difficulty))
+    .bind(lamp)
+    .bind(score.ex_score.map(|x| x as i64))
+    .bind(score.miss_count.map(|x| x as i64))
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn play_side_str(play_side: PlaySide) -> &'static str {
+    match play_side {
+        PlaySide::Single => "SP",
+        PlaySide::Double => "DP",
+    }
+}
+
+fn difficulty_str(difficulty: Difficulty) -> &'static str {
+    match difficulty {
+        Difficulty::Beginner => "BEGINNER",
+        Difficulty::Normal => "NORMAL",
+        Difficulty::Hyper => "HYPER",
+        Difficulty::Another => "ANOTHER",
+        Difficulty::Leggendaria => "LEGGENDARIA",
+    }
+}
+
+fn platform_str(platform: Platform) -> &'static str {
+    match platform {
+        Platform::Arcade => "ARCADE",
+        Platform::Infinitas => "INFINITAS",
+        Platform::Consumer => "CONSUMER",
+    }
+}
+
+/// Splits tempo into `min_bpm` and `max_bpm` columns.
+fn bpm_columns(bpm: Bpm) -> (Option<i64>, Option<i64>) {
+    match bpm {
+        Bpm::Fixed(b) => (None, Some(b as i64)),
+        Bpm::Range(min, max) => (Some(min as i64), Some(max as i64)),
+        Bpm::Indeterminate => (None, None),
+    }
+}
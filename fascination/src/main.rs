@@ -1,20 +1,37 @@
+mod alias;
+mod availability;
 mod cli;
 mod data;
 mod db;
+mod matcher;
 mod parser;
+mod score;
+mod tier;
 
 use crate::{
-    cli::Arguments,
-    data::Subheader,
-    db::{insert_diffs, insert_song},
-    parser::parse_song_tr,
+    alias::read_alias_csv,
+    availability::{fold_periods, read_availability_events},
+    cli::{
+        AliasesArguments, Arguments, AvailabilityArguments, Command, ScoresArguments,
+        SongsArguments, TiersArguments,
+    },
+    data::{AvailabilityEvent, AvailabilityPeriod, Platform, Subheader},
+    db::{
+        diff_exists, fetch_debut_version_number, fetch_title_pairs, insert_alias,
+        insert_debut_availability, insert_diffs, insert_song, insert_tier, replace_availabilities,
+        reset_tier_table, update_diff_added_version,
+    },
+    matcher::{TitleMatch, TitleMatcher},
+    parser::{parse_play_side, parse_song_tr},
+    score::read_score_csv,
+    tier::read_tier_list,
 };
 
-use std::fs::read_to_string;
+use std::{collections::HashMap, fs::read_to_string};
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use db::{insert_version, open_sqlite_file};
+use db::{insert_version, open_sqlite_file, open_userdata_file, upsert_score};
 use once_cell::sync::Lazy;
 use parser::{parse_subheader, parse_version};
 use scraper::{Html, Selector};
@@ -27,7 +44,20 @@ static SELECTOR_TD: Lazy<Selector> = Lazy::new(|| Selector::parse("td").expect("
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Arguments::parse();
+    let command = args
+        .command
+        .or(args.songs.map(Command::Songs))
+        .expect("clap requires arguments");
+    match command {
+        Command::Songs(songs_args) => import_songs(songs_args).await,
+        Command::Tiers(tiers_args) => import_tiers(tiers_args).await,
+        Command::Scores(scores_args) => import_scores(scores_args).await,
+        Command::Availability(availability_args) => import_availability(availability_args).await,
+        Command::Aliases(aliases_args) => import_aliases(aliases_args).await,
+    }
+}
 
+async fn import_songs(args: SongsArguments) -> Result<()> {
     let table_html = read_to_string(&args.table_html)?;
     let table_fragment = Html::parse_fragment(&table_html);
     let tbody = table_fragment
@@ -46,6 +76,9 @@ async fn main() -> Result<()> {
     };
     let mut event = None;
 
+    // charts added later may refer to versions which appear later in the table
+    let mut later_diffs = vec![];
+
     for tr in tbody.select(&SELECTOR_TR) {
         let tds: Vec<_> = tr.select(&SELECTOR_TD).collect();
         match tds.len() {
@@ -65,24 +98,233 @@ async fn main() -> Result<()> {
             13 => {
                 let (song, diffs) = parse_song_tr(&tds)?;
 
-                let song_id = insert_song(
-                    &sqlite_pool,
-                    version_id.context("version unset")?,
-                    event.as_deref(),
-                    &song,
-                )
-                .await?;
+                let version_id = version_id.context("version unset")?;
+                let song_id =
+                    insert_song(&sqlite_pool, version_id, event.as_deref(), &song).await?;
 
                 insert_diffs(&sqlite_pool, song_id, &diffs).await?;
+                insert_debut_availability(&sqlite_pool, song_id, version_id).await?;
                 println!(
                     "song inserted: {} ({song_id}), {} diffs",
                     song.title,
                     diffs.len()
                 );
+
+                let added_later = diffs
+                    .into_iter()
+                    .filter(|d| d.added_version_number.is_some());
+                later_diffs.extend(added_later.map(|d| (song_id, song.title.clone(), d)));
             }
             _ => continue,
         }
     }
 
+    for (song_id, title, diff) in later_diffs {
+        let Some(version_number) = diff.added_version_number else {
+            continue;
+        };
+        if !update_diff_added_version(&sqlite_pool, song_id, &diff, version_number).await? {
+            println!(
+                "unknown version: {title} [{:?} {:?}] {version_number}",
+                diff.play_side, diff.difficulty
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn import_tiers(args: TiersArguments) -> Result<()> {
+    let tiers = read_tier_list(&args.tier_list)?;
+    let sqlite_pool = open_sqlite_file(&args.sqlite_file).await?;
+    let title_pairs = fetch_title_pairs(&sqlite_pool).await?;
+
+    // a failed import keeps the previous tiers of the table
+    let mut tx = sqlite_pool.begin().await?;
+    let tier_table_id = reset_tier_table(&mut tx, &args.abbrev, &args.name).await?;
+    println!("tier table: {} ({tier_table_id})", args.name);
+
+    // tiers are ordered by their first appearance
+    let mut tier_labels: Vec<&str> = vec![];
+    let mut unmatched = vec![];
+    for tier in &tiers {
+        let tier_order = match tier_labels.iter().position(|l| *l == tier.tier) {
+            Some(order) => order,
+            None => {
+                tier_labels.push(&tier.tier);
+                tier_labels.len() - 1
+            }
+        };
+
+        let song_id = title_pairs
+            .iter()
+            .find(|(_, title)| *title == tier.title)
+            .map(|(id, _)| *id);
+        let Some(song_id) = song_id else {
+            unmatched.push(tier);
+            continue;
+        };
+        if !diff_exists(&sqlite_pool, song_id, tier.play_side, tier.difficulty).await? {
+            unmatched.push(tier);
+            continue;
+        }
+
+        insert_tier(&mut tx, tier_table_id, song_id, tier, tier_order).await?;
+    }
+    tx.commit().await?;
+
+    println!(
+        "{} tiers inserted, {} unmatched",
+        tiers.len() - unmatched.len(),
+        unmatched.len()
+    );
+    for tier in unmatched {
+        println!(
+            "unmatched: {} [{:?} {:?}] {}",
+            tier.title, tier.play_side, tier.difficulty, tier.tier
+        );
+    }
+
+    Ok(())
+}
+
+async fn import_scores(args: ScoresArguments) -> Result<()> {
+    let play_side = parse_play_side(&args.play_side)?;
+    let scores = read_score_csv(&args.score_csv)?;
+    let sqlite_pool = open_sqlite_file(&args.sqlite_file).await?;
+    let userdata_pool = open_userdata_file(&args.userdata_file).await?;
+    let title_pairs = fetch_title_pairs(&sqlite_pool).await?;
+    let matcher = TitleMatcher::new(&title_pairs, args.max_distance);
+
+    let mut upserted = 0;
+    let mut unmatched = vec![];
+    for score in &scores {
+        let song_id = match matcher.find(&score.title) {
+            TitleMatch::Exact(id) => id,
+            TitleMatch::Fuzzy(id, matched, distance) => {
+                println!("fuzzy matched: {} -> {matched} ({distance})", score.title);
+                id
+            }
+            TitleMatch::Unmatched => {
+                unmatched.push(score);
+                continue;
+            }
+        };
+        if !diff_exists(&sqlite_pool, song_id, play_side, score.difficulty).await? {
+            unmatched.push(score);
+            continue;
+        }
+
+        upsert_score(&userdata_pool, &args.user, song_id, play_side, score).await?;
+        upserted += 1;
+    }
+
+    println!("{upserted} scores upserted, {} unmatched", unmatched.len());
+    for score in unmatched {
+        println!(
+            "unmatched: {} [{:?} {:?}] {:?}",
+            score.title, play_side, score.difficulty, score.lamp
+        );
+    }
+
+    Ok(())
+}
+
+async fn import_availability(args: AvailabilityArguments) -> Result<()> {
+    let events = read_availability_events(&args.event_csv)?;
+    let sqlite_pool = open_sqlite_file(&args.sqlite_file).await?;
+    let title_pairs = fetch_title_pairs(&sqlite_pool).await?;
+    let matcher = TitleMatcher::new(&title_pairs, args.max_distance);
+
+    // events are grouped by song and platform, keeping their order
+    let mut keys = vec![];
+    let mut grouped: HashMap<(i64, Platform), Vec<&AvailabilityEvent>> = HashMap::new();
+    let mut unmatched = vec![];
+    for event in &events {
+        let song_id = match matcher.find(&event.title) {
+            TitleMatch::Exact(id) => id,
+            TitleMatch::Fuzzy(id, matched, distance) => {
+                println!("fuzzy matched: {} -> {matched} ({distance})", event.title);
+                id
+            }
+            TitleMatch::Unmatched => {
+                unmatched.push(event);
+                continue;
+            }
+        };
+
+        let key = (song_id, event.platform);
+        if !grouped.contains_key(&key) {
+            keys.push(key);
+        }
+        grouped.entry(key).or_default().push(event);
+    }
+
+    for key @ (song_id, platform) in keys {
+        // every song starts on ARCADE from its debut
+        let initial = if platform == Platform::Arcade {
+            let debut = fetch_debut_version_number(&sqlite_pool, song_id).await?;
+            vec![AvailabilityPeriod {
+                added_version_number: Some(debut),
+                deleted: false,
+                deleted_version_number: None,
+            }]
+        } else {
+            vec![]
+        };
+        let periods = fold_periods(initial, &grouped[&key])?;
+
+        replace_availabilities(&sqlite_pool, song_id, platform, &periods).await?;
+        println!(
+            "availability updated: {} [{platform:?}], {} periods",
+            grouped[&key][0].title,
+            periods.len()
+        );
+    }
+
+    println!("{} events unmatched", unmatched.len());
+    for event in unmatched {
+        println!(
+            "unmatched: {} [{:?}] {:?}",
+            event.title, event.platform, event.kind
+        );
+    }
+
+    Ok(())
+}
+
+async fn import_aliases(args: AliasesArguments) -> Result<()> {
+    let aliases = read_alias_csv(&args.alias_csv)?;
+    let sqlite_pool = open_sqlite_file(&args.sqlite_file).await?;
+    let title_pairs = fetch_title_pairs(&sqlite_pool).await?;
+    let matcher = TitleMatcher::new(&title_pairs, args.max_distance);
+
+    let mut inserted = 0;
+    let mut unmatched = vec![];
+    for alias in &aliases {
+        let song_id = match matcher.find(&alias.title) {
+            TitleMatch::Exact(id) => id,
+            TitleMatch::Fuzzy(id, matched, distance) => {
+                println!("fuzzy matched: {} -> {matched} ({distance})", alias.title);
+                id
+            }
+            TitleMatch::Unmatched => {
+                unmatched.push(alias);
+                continue;
+            }
+        };
+
+        insert_alias(&sqlite_pool, song_id, alias).await?;
+        inserted += 1;
+    }
+
+    println!("{inserted} aliases inserted, {} unmatched", unmatched.len());
+    for alias in unmatched {
+        println!(
+            "unmatched: {} [{:?}] {}",
+            alias.title, alias.kind, alias.alias
+        );
+    }
+
     Ok(())
 }
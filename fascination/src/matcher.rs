@@ -0,0 +1,42 @@
+use lyricism::{query_delete, query_insert, query_replace, query_substring, Lyricism};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TitleMatch<'a> {
+    Exact(i64),
+    Fuzzy(i64, &'a str, isize),
+    Unmatched,
+}
+
+/// Finds songs by titles which may differ slightly from the catalog.
+pub struct TitleMatcher<'a> {
+    title_pairs: &'a [(i64, String)],
+    max_distance: isize,
+}
+
+impl<'a> TitleMatcher<'a> {
+    pub fn new(title_pairs: &'a [(i64, String)], max_distance: isize) -> TitleMatcher<'a> {
+        TitleMatcher {
+            title_pairs,
+            max_distance,
+        }
+    }
+
+    pub fn find(&self, title: &str) -> TitleMatch<'a> {
+        if let Some((id, _)) = self.title_pairs.iter().find(|(_, t)| t == title) {
+            return TitleMatch::Exact(*id);
+        }
+
+        let searcher = Lyricism::new(query_insert, query_delete, query_replace, query_substring);
+        let nearest = self
+            .title_pairs
+            .iter()
+            .map(|(id, t)| (searcher.distance(title, t), *id, t.as_str()))
+            .min_by_key(|(d, _, _)| *d);
+        match nearest {
+            Some((distance, id, matched)) if distance <= self.max_distance => {
+                TitleMatch::Fuzzy(id, matched, distance)
+            }
+            _ => TitleMatch::Unmatched,
+        }
+    }
+}
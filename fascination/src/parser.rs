@@ -1,6 +1,8 @@
-use crate::data::{Diff, Difficulty, NoteType, PlaySide, ScratchType, Song, Subheader, Version};
+use crate::data::{
+    Bpm, Diff, Difficulty, NoteType, PlaySide, ScratchType, Song, Subheader, Version,
+};
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use scraper::ElementRef;
 
 pub fn parse_song_tr(tds: &[ElementRef]) -> Result<(Song, Vec<Diff>)> {
@@ -19,19 +21,7 @@ pub fn parse_song_tr(tds: &[ElementRef]) -> Result<(Song, Vec<Diff>)> {
     ];
     let diffs = raw_diffs.into_iter().flatten().collect();
 
-    let (min_bpm, max_bpm) = {
-        let mut bpm_text = tds[9].text().next().unwrap_or_default().split('-');
-        let first = bpm_text.next();
-        let second = bpm_text.next();
-        match (first, second) {
-            // Indeterminate tempo
-            (Some("※"), None) => (None, 0usize),
-
-            (Some(min), Some(max)) => (Some(min.parse()?), max.parse()?),
-            (Some(bpm), None) => (None, bpm.parse()?),
-            _ => (None, 0usize),
-        }
-    };
+    let bpm = parse_bpm(tds[9].text().next().unwrap_or_default())?;
     let genre = tds[10].text().next().unwrap_or_default().trim().to_string();
     let title = tds[11]
         .text()
@@ -46,8 +36,7 @@ pub fn parse_song_tr(tds: &[ElementRef]) -> Result<(Song, Vec<Diff>)> {
             genre,
             title,
             artist,
-            min_bpm,
-            max_bpm,
+            bpm,
         },
         diffs,
     ))
@@ -55,6 +44,9 @@ pub fn parse_song_tr(tds: &[ElementRef]) -> Result<(Song, Vec<Diff>)> {
 
 fn parse_diff_td(play_side: PlaySide, difficulty: Difficulty, td: ElementRef) -> Option<Diff> {
     let mut level = 0;
+    let mut notes = None;
+    let mut bpm = None;
+    let mut added_version_number = None;
     let mut note_type = None;
     let mut scratch_type = None;
 
@@ -80,11 +72,39 @@ fn parse_diff_td(play_side: PlaySide, difficulty: Difficulty, td: ElementRef) ->
             "[MSS]" => {
                 scratch_type = Some(ScratchType::Multi);
             }
+            // chart-specific tempo, like "[BPM:150-300]"
+            t if t.starts_with("[BPM") => {
+                let bpm_text = t
+                    .trim_start_matches("[BPM")
+                    .trim_end_matches(']')
+                    .trim_start_matches(':')
+                    .trim();
+                bpm = parse_bpm(bpm_text).ok();
+            }
+            // chart added in later version, like "[VER:31]"
+            t if t.starts_with("[VER") => {
+                let version_text = t
+                    .trim_start_matches("[VER")
+                    .trim_end_matches(']')
+                    .trim_start_matches(':')
+                    .trim();
+                added_version_number = version_text.parse().ok();
+            }
             t if t.contains('[') => {
                 // eprintln!("Unknown type: {t}");
             }
             l => {
-                level = l.parse().unwrap_or(0);
+                // notes count may follow in parentheses, like "12(1853)" or "(1853)"
+                let (level_text, notes_text) = match l.split_once('(') {
+                    Some((lt, nt)) => (lt.trim(), Some(nt.trim_end_matches(')').trim())),
+                    None => (l.trim(), None),
+                };
+                if !level_text.is_empty() {
+                    level = level_text.parse().unwrap_or(0);
+                }
+                if let Some(nt) = notes_text {
+                    notes = nt.parse().ok();
+                }
             }
         }
     }
@@ -93,11 +113,29 @@ fn parse_diff_td(play_side: PlaySide, difficulty: Difficulty, td: ElementRef) ->
         play_side,
         difficulty,
         level,
+        notes,
+        bpm,
+        added_version_number,
         note_type,
         scratch_type,
     })
 }
 
+fn parse_bpm(bpm_text: &str) -> Result<Bpm> {
+    let mut parts = bpm_text.trim().split('-');
+    let first = parts.next();
+    let second = parts.next();
+    let bpm = match (first, second) {
+        // Indeterminate tempo
+        (Some("※"), None) => Bpm::Indeterminate,
+
+        (Some(min), Some(max)) => Bpm::Range(min.parse()?, max.parse()?),
+        (Some(bpm), None) => Bpm::Fixed(bpm.parse()?),
+        _ => Bpm::Indeterminate,
+    };
+    Ok(bpm)
+}
+
 pub fn parse_subheader(tds: &[ElementRef]) -> Result<Subheader> {
     let all_text = tds[0].text().collect::<Vec<_>>().concat();
     ensure!(!all_text.trim().is_empty(), "invalid subheader text");
@@ -160,3 +198,108 @@ pub fn parse_version(version_str: &str) -> Result<Version> {
         number,
     })
 }
+
+pub fn parse_play_side(play_side_str: &str) -> Result<PlaySide> {
+    match play_side_str.trim().to_ascii_uppercase().as_str() {
+        "SP" | "S" => Ok(PlaySide::Single),
+        "DP" | "D" => Ok(PlaySide::Double),
+        other => bail!("unknown play side: {other}"),
+    }
+}
+
+pub fn parse_difficulty(difficulty_str: &str) -> Result<Difficulty> {
+    match difficulty_str.trim().to_ascii_uppercase().as_str() {
+        "BEGINNER" | "B" => Ok(Difficulty::Beginner),
+        "NORMAL" | "N" => Ok(Difficulty::Normal),
+        "HYPER" | "H" => Ok(Difficulty::Hyper),
+        "ANOTHER" | "A" => Ok(Difficulty::Another),
+        "LEGGENDARIA" | "L" => Ok(Difficulty::Leggendaria),
+        other => bail!("unknown difficulty: {other}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_song_tr;
+    use crate::data::{Bpm, Diff, Difficulty, PlaySide, Song};
+
+    use scraper::{Html, Selector};
+
+    /// Parses cells of a song row written as `<td>` elements.
+    fn parse_row(tds_html: &str) -> (Song, Vec<Diff>) {
+        let html = Html::parse_fragment(&format!(
+            "<table><tbody><tr>{tds_html}</tr></tbody></table>"
+        ));
+        let selector = Selector::parse("td").expect("invalid selector");
+        let tds: Vec<_> = html.select(&selector).collect();
+        parse_song_tr(&tds).expect("row should be parsed")
+    }
+
+    fn find_diff(diffs: &[Diff], play_side: PlaySide, difficulty: Difficulty) -> &Diff {
+        diffs
+            .iter()
+            .find(|d| d.play_side == play_side && d.difficulty == difficulty)
+            .expect("diff should exist")
+    }
+
+    #[test]
+    fn parses_chart_bpm_markup() {
+        let (song, diffs) = parse_row(
+            "<td>-</td><td>3</td><td>7(900)<br>[BPM:150-300]</td><td>11<br>[BPM:※]</td><td>-</td>\
+             <td>4</td><td>8</td><td>12<br>[BPM: 180 ]</td><td>-</td>\
+             <td>150-200</td><td>GENRE</td><td>Title</td><td>Artist</td>",
+        );
+
+        assert_eq!(song.bpm, Bpm::Range(150, 200));
+        assert_eq!(diffs.len(), 6);
+
+        let sph = find_diff(&diffs, PlaySide::Single, Difficulty::Hyper);
+        assert_eq!(sph.level, 7);
+        assert_eq!(sph.notes, Some(900));
+        assert_eq!(sph.bpm, Some(Bpm::Range(150, 300)));
+
+        let spa = find_diff(&diffs, PlaySide::Single, Difficulty::Another);
+        assert_eq!(spa.level, 11);
+        assert_eq!(spa.bpm, Some(Bpm::Indeterminate));
+
+        let dpa = find_diff(&diffs, PlaySide::Double, Difficulty::Another);
+        assert_eq!(dpa.bpm, Some(Bpm::Fixed(180)));
+
+        let spn = find_diff(&diffs, PlaySide::Single, Difficulty::Normal);
+        assert_eq!(spn.bpm, None);
+    }
+
+    #[test]
+    fn parses_indeterminate_song_bpm() {
+        let (song, _) = parse_row(
+            "<td>-</td><td>3</td><td>7</td><td>11</td><td>-</td>\
+             <td>4</td><td>8</td><td>12</td><td>-</td>\
+             <td>※</td><td>GENRE</td><td>Title</td><td>Artist</td>",
+        );
+
+        assert_eq!(song.bpm, Bpm::Indeterminate);
+    }
+
+    #[test]
+    fn parses_chart_version_markup() {
+        let (_, diffs) = parse_row(
+            "<td>-</td><td>3</td><td>7</td><td>11</td><td>12<br>[VER:31]</td>\
+             <td>4</td><td>8</td><td>12<br>[BPM:200]<br>[VER: 29 ]</td><td>12<br>[VER:?]</td>\
+             <td>150</td><td>GENRE</td><td>Title</td><td>Artist</td>",
+        );
+
+        let spl = find_diff(&diffs, PlaySide::Single, Difficulty::Leggendaria);
+        assert_eq!(spl.level, 12);
+        assert_eq!(spl.added_version_number, Some(31));
+
+        let dpa = find_diff(&diffs, PlaySide::Double, Difficulty::Another);
+        assert_eq!(dpa.bpm, Some(Bpm::Fixed(200)));
+        assert_eq!(dpa.added_version_number, Some(29));
+
+        let dpl = find_diff(&diffs, PlaySide::Double, Difficulty::Leggendaria);
+        assert_eq!(dpl.added_version_number, None);
+
+        let spa = find_diff(&diffs, PlaySide::Single, Difficulty::Another);
+        assert_eq!(spa.added_version_number, None);
+    }
+}
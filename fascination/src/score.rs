@@ -0,0 +1,155 @@
+use crate::data::{Difficulty, Lamp, Score};
+
+use std::{io::Read, path::Path};
+
+use anyhow::{bail, Context, Result};
+use csv::{Reader, StringRecord};
+
+const DIFFICULTY_COLUMNS: &[(&str, Difficulty)] = &[
+    ("BEGINNER", Difficulty::Beginner),
+    ("NORMAL", Difficulty::Normal),
+    ("HYPER", Difficulty::Hyper),
+    ("ANOTHER", Difficulty::Another),
+    ("LEGGENDARIA", Difficulty::Leggendaria),
+];
+
+/// Reads score CSV exported from the official site.
+/// Diffs which do not exist or are not played are skipped.
+pub fn read_score_csv(path: &Path) -> Result<Vec<Score>> {
+    parse_score_csv(Reader::from_path(path)?)
+}
+
+fn parse_score_csv(mut reader: Reader<impl Read>) -> Result<Vec<Score>> {
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim() == name)
+            .with_context(|| format!("column not found: {name}"))
+    };
+
+    let title_column = column("タイトル")?;
+    let mut difficulty_columns = vec![];
+    for &(prefix, difficulty) in DIFFICULTY_COLUMNS {
+        difficulty_columns.push((
+            difficulty,
+            column(&format!("{prefix} 難易度"))?,
+            column(&format!("{prefix} スコア"))?,
+            column(&format!("{prefix} ミスカウント"))?,
+            column(&format!("{prefix} クリアタイプ"))?,
+        ));
+    }
+
+    let mut scores = vec![];
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |index: usize| record.get(index).unwrap_or_default().trim();
+        let title = field(title_column);
+
+        for &(difficulty, level, ex_score, miss_count, lamp) in &difficulty_columns {
+            if matches!(field(level), "" | "0") {
+                continue;
+            }
+            let lamp = parse_lamp(field(lamp))
+                .with_context(|| format!("invalid clear type at row {}", i + 1))?;
+            if lamp == Lamp::NoPlay {
+                continue;
+            }
+
+            scores.push(Score {
+                title: title.to_string(),
+                difficulty,
+                lamp,
+                ex_score: parse_count(&record, ex_score),
+                miss_count: parse_count(&record, miss_count),
+            });
+        }
+    }
+
+    Ok(scores)
+}
+
+fn parse_lamp(lamp_str: &str) -> Result<Lamp> {
+    let lamp = match lamp_str {
+        "NO PLAY" => Lamp::NoPlay,
+        "FAILED" => Lamp::Failed,
+        "ASSIST CLEAR" => Lamp::AssistClear,
+        "EASY CLEAR" => Lamp::EasyClear,
+        "CLEAR" => Lamp::Clear,
+        "HARD CLEAR" => Lamp::HardClear,
+        "EX HARD CLEAR" => Lamp::ExHardClear,
+        "FULLCOMBO CLEAR" => Lamp::FullCombo,
+        other => bail!("unknown clear type: {other}"),
+    };
+    Ok(lamp)
+}
+
+/// Parses numeric field. "---" means no record.
+fn parse_count(record: &StringRecord, index: usize) -> Option<usize> {
+    record.get(index)?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_lamp, parse_score_csv};
+    use crate::data::{Difficulty, Lamp, Score};
+
+    use csv::Reader;
+
+    /// Columns of every difficulty except ANOTHER, which is placed first to check the lookup by name.
+    const HEADER: &str = "ANOTHER クリアタイプ,ANOTHER ミスカウント,ANOTHER スコア,ANOTHER 難易度,\
+        バージョン,タイトル,\
+        BEGINNER 難易度,BEGINNER スコア,BEGINNER ミスカウント,BEGINNER クリアタイプ,\
+        NORMAL 難易度,NORMAL スコア,NORMAL ミスカウント,NORMAL クリアタイプ,\
+        HYPER 難易度,HYPER スコア,HYPER ミスカウント,HYPER クリアタイプ,\
+        LEGGENDARIA 難易度,LEGGENDARIA スコア,LEGGENDARIA ミスカウント,LEGGENDARIA クリアタイプ";
+
+    fn parse(rows: &[&str]) -> anyhow::Result<Vec<Score>> {
+        let csv = format!("{HEADER}\n{}\n", rows.join("\n"));
+        parse_score_csv(Reader::from_reader(csv.as_bytes()))
+    }
+
+    #[test]
+    fn reads_scores_by_header_names() {
+        let scores = parse(&["HARD CLEAR,3,2100,11,RESIDENT, Title ,\
+             0,0,---,NO PLAY,\
+             3,500,---,FULLCOMBO CLEAR,\
+             7,900,12,NO PLAY,\
+             0,0,---,NO PLAY"])
+        .expect("CSV should be parsed");
+
+        assert_eq!(
+            scores,
+            vec![
+                Score {
+                    title: "Title".into(),
+                    difficulty: Difficulty::Normal,
+                    lamp: Lamp::FullCombo,
+                    ex_score: Some(500),
+                    miss_count: None,
+                },
+                Score {
+                    title: "Title".into(),
+                    difficulty: Difficulty::Another,
+                    lamp: Lamp::HardClear,
+                    ex_score: Some(2100),
+                    miss_count: Some(3),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_columns_and_unknown_clear_types() {
+        let missing = parse_score_csv(Reader::from_reader("タイトル\nTitle\n".as_bytes()));
+        assert!(missing.is_err());
+
+        let unknown = parse(&[
+            "PERFECT,0,0,11,RESIDENT,Title,0,0,---,NO PLAY,0,0,---,NO PLAY,0,0,---,NO PLAY,0,0,---,NO PLAY",
+        ]);
+        assert!(unknown.is_err());
+
+        assert_eq!(parse_lamp("EX HARD CLEAR").ok(), Some(Lamp::ExHardClear));
+        assert!(parse_lamp("hard clear").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{
+    data::Tier,
+    parser::{parse_difficulty, parse_play_side},
+};
+
+use std::{fs::File, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawTier {
+    title: String,
+    play_side: String,
+    difficulty: String,
+    tier: String,
+}
+
+/// Reads tier list. Format is determined by the extension.
+pub fn read_tier_list(path: &Path) -> Result<Vec<Tier>> {
+    let raw_tiers: Vec<RawTier> = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => csv::Reader::from_path(path)?
+            .deserialize()
+            .collect::<Result<_, _>>()?,
+        Some("json") => serde_json::from_reader(File::open(path)?)?,
+        _ => bail!("tier list must be .csv or .json"),
+    };
+
+    raw_tiers
+        .into_iter()
+        .enumerate()
+        .map(|(i, raw)| convert_tier(raw).with_context(|| format!("invalid tier at row {}", i + 1)))
+        .collect()
+}
+
+fn convert_tier(raw: RawTier) -> Result<Tier> {
+    Ok(Tier {
+        title: raw.title.trim().to_string(),
+        play_side: parse_play_side(&raw.play_side)?,
+        difficulty: parse_difficulty(&raw.difficulty)?,
+        tier: raw.tier.trim().to_string(),
+    })
+}
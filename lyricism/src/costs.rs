@@ -1,3 +1,8 @@
+use crate::distance::Lyricism;
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
 const SIGN_CHARS: &[char] = &[
     '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/', ':', ';', '<', '=',
     '>', '?', '@', '[', '\\', ']', '^', '_', '`', '{', '|', '}', '~',
@@ -5,38 +10,121 @@ const SIGN_CHARS: &[char] = &[
 
 const LEET_PAIRS: &[(char, char)] = &[('e', '3'), ('o', '0'), ('a', 'V')];
 
-pub fn query_insert(c: char) -> usize {
-    if c.is_ascii_whitespace() {
-        1
-    } else if SIGN_CHARS.contains(&c) {
-        2
-    } else {
-        7
+/// Weights of edit operations used by the `query_*` cost functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct CostProfile {
+    pub insert_whitespace: usize,
+    pub insert_sign: usize,
+    pub insert_other: usize,
+    pub delete_whitespace: usize,
+    pub delete_other: usize,
+
+    /// Query character in lowercase of the target one.
+    pub replace_uppercase: usize,
+
+    /// Query character in uppercase of the target one.
+    pub replace_lowercase: usize,
+    pub replace_leet: usize,
+    pub replace_other: usize,
+
+    /// Bonus per character of the query found as a substring.
+    pub substring_bonus: isize,
+
+    /// Penalty is the substring position divided by this.
+    pub substring_position_divisor: usize,
+}
+
+impl CostProfile {
+    pub const DEFAULT: CostProfile = CostProfile {
+        insert_whitespace: 1,
+        insert_sign: 2,
+        insert_other: 7,
+        delete_whitespace: 2,
+        delete_other: 10,
+        replace_uppercase: 1,
+        replace_lowercase: 2,
+        replace_leet: 3,
+        replace_other: 4,
+        substring_bonus: 20,
+        substring_position_divisor: 2,
+    };
+
+    pub fn insert(&self, c: char) -> usize {
+        if c.is_ascii_whitespace() {
+            self.insert_whitespace
+        } else if SIGN_CHARS.contains(&c) {
+            self.insert_sign
+        } else {
+            self.insert_other
+        }
+    }
+
+    pub fn delete(&self, c: char) -> usize {
+        if c.is_ascii_whitespace() {
+            self.delete_whitespace
+        } else {
+            self.delete_other
+        }
+    }
+
+    pub fn replace(&self, qc: char, tc: char) -> usize {
+        if qc == tc {
+            0
+        } else if qc.to_ascii_uppercase() == tc {
+            self.replace_uppercase
+        } else if qc.to_ascii_lowercase() == tc {
+            self.replace_lowercase
+        } else if LEET_PAIRS.contains(&(qc, tc)) {
+            self.replace_leet
+        } else {
+            self.replace_other
+        }
+    }
+
+    pub fn substring(&self, s: &str, position: usize) -> isize {
+        s.chars().count() as isize * -self.substring_bonus
+            + (position / self.substring_position_divisor) as isize
+    }
+
+    /// Creates a `Lyricism` which uses this profile.
+    #[allow(clippy::type_complexity)]
+    pub fn searcher(
+        &self,
+    ) -> Lyricism<
+        impl Fn(char) -> usize + '_,
+        impl Fn(char) -> usize + '_,
+        impl Fn(char, char) -> usize + '_,
+        impl Fn(&str, usize) -> isize + '_,
+    > {
+        Lyricism::new(
+            |c| self.insert(c),
+            |c| self.delete(c),
+            |qc, tc| self.replace(qc, tc),
+            |s, position| self.substring(s, position),
+        )
     }
 }
 
-pub fn query_delete(c: char) -> usize {
-    if c.is_ascii_whitespace() {
-        2
-    } else {
-        10
+impl Default for CostProfile {
+    fn default() -> CostProfile {
+        CostProfile::DEFAULT
     }
 }
 
+pub fn query_insert(c: char) -> usize {
+    CostProfile::DEFAULT.insert(c)
+}
+
+pub fn query_delete(c: char) -> usize {
+    CostProfile::DEFAULT.delete(c)
+}
+
 pub fn query_replace(qc: char, tc: char) -> usize {
-    if qc == tc {
-        0
-    } else if qc.to_ascii_uppercase() == tc {
-        1
-    } else if qc.to_ascii_lowercase() == tc {
-        2
-    } else if LEET_PAIRS.contains(&(qc, tc)) {
-        3
-    } else {
-        4
-    }
+    CostProfile::DEFAULT.replace(qc, tc)
 }
 
 pub fn query_substring(s: &str, position: usize) -> isize {
-    s.chars().count() as isize * -20 + (position as isize / 2)
+    CostProfile::DEFAULT.substring(s, position)
 }
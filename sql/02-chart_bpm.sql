@@ -0,0 +1,58 @@
+-- indeterminate tempo ("※" in tables), "max_bpm" of songs is NULL then
+-- run with foreign keys disabled, as "songs" is rebuilt to make "max_bpm" nullable
+CREATE TABLE "songs_new" (
+    "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+    "version_id" INTEGER NOT NULL REFERENCES "versions"("id"),
+    "genre" TEXT NOT NULL,
+    "title" TEXT NOT NULL,
+    "artist" TEXT NOT NULL,
+    "min_bpm" INTEGER NULL,
+    "max_bpm" INTEGER NULL,
+    "unlock_info" TEXT NULL,
+    "bpm_indeterminate" INTEGER NOT NULL DEFAULT 0
+);
+INSERT INTO "songs_new"
+SELECT
+    "id",
+    "version_id",
+    "genre",
+    "title",
+    "artist",
+    "min_bpm",
+    NULLIF("max_bpm", 0),
+    "unlock_info",
+    "max_bpm" = 0
+FROM "songs";
+DROP TABLE "songs";
+ALTER TABLE "songs_new" RENAME TO "songs";
+CREATE INDEX "songs_foreign_versions" ON "songs" ("version_id");
+
+-- chart-specific tempo, overrides the song's one when "max_bpm" is set or "bpm_indeterminate" is true
+ALTER TABLE "diffs" ADD COLUMN "min_bpm" INTEGER NULL;
+ALTER TABLE "diffs" ADD COLUMN "max_bpm" INTEGER NULL;
+ALTER TABLE "diffs" ADD COLUMN "bpm_indeterminate" INTEGER NOT NULL DEFAULT 0;
+
+-- effective tempo of each chart, "min_bpm" and "max_bpm" are NULL if indeterminate
+CREATE VIEW "chart_bpms" AS
+SELECT
+    "song_id",
+    "play_side",
+    "difficulty",
+    "indeterminate",
+    CASE WHEN "indeterminate" THEN NULL ELSE "min_bpm" END AS "min_bpm",
+    CASE WHEN "indeterminate" THEN NULL ELSE "max_bpm" END AS "max_bpm"
+FROM (
+    SELECT
+        "diffs"."song_id",
+        "diffs"."play_side",
+        "diffs"."difficulty",
+        CASE
+            WHEN "diffs"."bpm_indeterminate" THEN 1
+            WHEN "diffs"."max_bpm" IS NOT NULL THEN 0
+            ELSE "songs"."bpm_indeterminate"
+        END AS "indeterminate",
+        CASE WHEN "diffs"."max_bpm" IS NULL THEN "songs"."min_bpm" ELSE "diffs"."min_bpm" END AS "min_bpm",
+        COALESCE("diffs"."max_bpm", "songs"."max_bpm") AS "max_bpm"
+    FROM "diffs"
+    INNER JOIN "songs" ON "diffs"."song_id" = "songs"."id"
+);
@@ -0,0 +1,20 @@
+-- community difficulty table (e.g. SP12 hard clear tiers)
+CREATE TABLE "tier_tables" (
+    "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+    "name" TEXT NOT NULL,
+    "abbrev" TEXT NOT NULL UNIQUE
+);
+
+-- diff tier in the table
+-- "tier_order" follows the order in the source list
+CREATE TABLE "tiers" (
+    "tier_table_id" INTEGER NOT NULL REFERENCES "tier_tables"("id"),
+    "song_id" INTEGER NOT NULL,
+    "play_side" TEXT NOT NULL,
+    "difficulty" TEXT NOT NULL,
+    "tier" TEXT NOT NULL,
+    "tier_order" INTEGER NOT NULL,
+    PRIMARY KEY ("tier_table_id", "song_id", "play_side", "difficulty"),
+    FOREIGN KEY ("song_id", "play_side", "difficulty") REFERENCES "diffs"("song_id", "play_side", "difficulty")
+);
+CREATE INDEX "tiers_foreign_diffs" ON "tiers" ("song_id", "play_side", "difficulty");
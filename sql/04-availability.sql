@@ -0,0 +1,28 @@
+-- platform where songs are played
+CREATE TABLE "platforms" (
+    "name" TEXT NOT NULL PRIMARY KEY
+);
+INSERT INTO "platforms" ("name")
+VALUES
+    ('ARCADE'),
+    ('INFINITAS'),
+    ('CONSUMER');
+
+-- period in which the song is playable on the platform
+-- a song deleted and revived later has multiple periods
+-- "added_version_number" and "deleted_version_number" refer to "versions"("number") on ARCADE,
+-- and may be NULL on other platforms which do not follow arcade versions
+CREATE TABLE "availabilities" (
+    "song_id" INTEGER NOT NULL REFERENCES "songs"("id"),
+    "platform" TEXT NOT NULL REFERENCES "platforms"("name"),
+    "added_version_number" INTEGER NULL,
+    "deleted" BOOLEAN NOT NULL DEFAULT FALSE,
+    "deleted_version_number" INTEGER NULL
+);
+CREATE INDEX "availabilities_song_platform" ON "availabilities" ("song_id", "platform");
+
+-- every song has been playable on ARCADE since its debut
+INSERT INTO "availabilities" ("song_id", "platform", "added_version_number")
+SELECT "songs"."id", 'ARCADE', "versions"."number"
+FROM "songs"
+INNER JOIN "versions" ON "songs"."version_id" = "versions"."id";
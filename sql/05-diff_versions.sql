@@ -0,0 +1,2 @@
+-- version in which the chart was added, NULL if it debuted with the song
+ALTER TABLE "diffs" ADD COLUMN "added_version_id" INTEGER NULL REFERENCES "versions"("id");
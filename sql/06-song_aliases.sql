@@ -0,0 +1,8 @@
+-- alternative names of songs used for suggestions
+-- "kind" is READING (kana reading of the title) or ALIAS (common nickname)
+CREATE TABLE "song_aliases" (
+    "song_id" INTEGER NOT NULL REFERENCES "songs"("id"),
+    "kind" TEXT NOT NULL,
+    "alias" TEXT NOT NULL,
+    PRIMARY KEY ("song_id", "kind", "alias")
+);
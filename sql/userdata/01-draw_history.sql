@@ -0,0 +1,12 @@
+-- diffs drawn by random queries
+CREATE TABLE "draw_history" (
+    "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+    "channel_id" TEXT NOT NULL,
+    "user_id" TEXT NOT NULL,
+    "song_id" INTEGER NOT NULL,
+    "play_side" TEXT NOT NULL,
+    "difficulty" TEXT NOT NULL,
+    "drawn_at" INTEGER NOT NULL
+);
+CREATE INDEX "draw_history_channels" ON "draw_history" ("channel_id", "id");
+CREATE INDEX "draw_history_users" ON "draw_history" ("user_id", "id");
@@ -0,0 +1,13 @@
+-- clear lamps and scores of each user
+-- "lamp" is 0 (NO PLAY) to 7 (FULLCOMBO CLEAR)
+CREATE TABLE "scores" (
+    "user_name" TEXT NOT NULL,
+    "song_id" INTEGER NOT NULL,
+    "play_side" TEXT NOT NULL,
+    "difficulty" TEXT NOT NULL,
+    "lamp" INTEGER NOT NULL,
+    "ex_score" INTEGER NULL,
+    "miss_count" INTEGER NULL,
+    "updated_at" INTEGER NOT NULL,
+    PRIMARY KEY ("user_name", "song_id", "play_side", "difficulty")
+);
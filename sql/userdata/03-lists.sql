@@ -0,0 +1,18 @@
+-- named diff lists owned by a user or a channel
+-- "scope" is 'user' or 'channel', and "owner" is the user name or the channel ID
+CREATE TABLE "lists" (
+    "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+    "scope" TEXT NOT NULL,
+    "owner" TEXT NOT NULL,
+    "name" TEXT NOT NULL,
+    UNIQUE ("scope", "owner", "name")
+);
+
+CREATE TABLE "list_entries" (
+    "list_id" INTEGER NOT NULL REFERENCES "lists"("id") ON DELETE CASCADE,
+    "song_id" INTEGER NOT NULL,
+    "play_side" TEXT NOT NULL,
+    "difficulty" TEXT NOT NULL,
+    "added_at" INTEGER NOT NULL,
+    PRIMARY KEY ("list_id", "song_id", "play_side", "difficulty")
+);